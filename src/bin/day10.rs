@@ -1,82 +1,678 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
 const INPUT: &str = include_str!("day10_input.txt");
 
-fn gen_cycles(input: &str) -> Vec<i32> {
-    let mut cycles_history = vec![];
-    let mut x = 1;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Register {
+    X,
+    Y,
+}
 
-    for l in input.lines() {
-        let s: Vec<_> = l.split(' ').collect();
+impl FromStr for Register {
+    type Err = ();
 
-        let opcode = s[0];
-        let value = if s.len() == 2 {
-            Some(s[1].parse::<i32>().unwrap())
-        } else {
-            None
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" => Ok(Register::X),
+            "y" => Ok(Register::Y),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::X => write!(f, "x"),
+            Register::Y => write!(f, "y"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Registers {
+    x: i32,
+    y: i32,
+}
+
+impl Registers {
+    fn get(&self, register: Register) -> i32 {
+        match register {
+            Register::X => self.x,
+            Register::Y => self.y,
+        }
+    }
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers { x: 1, y: 0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OperandKind {
+    Value,
+    Register,
+    // Instruction address, or a label in assembly source
+    Target,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operand {
+    Value(i32),
+    Register(Register),
+    Target(usize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{value}"),
+            Operand::Register(register) => write!(f, "{register}"),
+            Operand::Target(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+impl Operand {
+    fn kind(self) -> OperandKind {
+        match self {
+            Operand::Value(_) => OperandKind::Value,
+            Operand::Register(_) => OperandKind::Register,
+            Operand::Target(_) => OperandKind::Target,
+        }
+    }
+
+    // Operands always have the kinds their opcode expects, `Instruction::new`
+    // checks it
+    fn value(self) -> i32 {
+        let Operand::Value(value) = self else {
+            unreachable!()
         };
+        value
+    }
 
-        let mut values = match opcode {
-            "noop" => {
-                vec![x]
-            }
-            "addx" => {
-                let values = vec![x, x];
+    fn register(self) -> Register {
+        let Operand::Register(register) = self else {
+            unreachable!()
+        };
+        register
+    }
 
-                x += value.unwrap();
+    fn target(self) -> usize {
+        let Operand::Target(target) = self else {
+            unreachable!()
+        };
+        target
+    }
+}
+
+const MAX_OPERANDS: usize = 2;
+
+struct Opcode {
+    mnemonic: &'static str,
+    operands: &'static [OperandKind],
+    cycles: usize,
+    // Runs at the end of the last cycle, returns the address of the next
+    // instruction when it isn't the following one
+    execute: fn(&[Operand], &mut Registers) -> Option<usize>,
+}
+
+// A single entry defines an instruction, from its syntax to its effect
+const OPCODES: &[Opcode] = &[
+    Opcode {
+        mnemonic: "noop",
+        operands: &[],
+        cycles: 1,
+        execute: |_, _| None,
+    },
+    Opcode {
+        mnemonic: "addx",
+        operands: &[OperandKind::Value],
+        cycles: 2,
+        execute: |operands, registers| {
+            registers.x += operands[0].value();
+            None
+        },
+    },
+    Opcode {
+        mnemonic: "addy",
+        operands: &[OperandKind::Value],
+        cycles: 2,
+        execute: |operands, registers| {
+            registers.y += operands[0].value();
+            None
+        },
+    },
+    // Jumps to the target unless the register is zero
+    Opcode {
+        mnemonic: "jnz",
+        operands: &[OperandKind::Register, OperandKind::Target],
+        cycles: 2,
+        execute: |operands, registers| {
+            Some(operands[1].target()).filter(|_| registers.get(operands[0].register()) != 0)
+        },
+    },
+    Opcode {
+        mnemonic: "jmp",
+        operands: &[OperandKind::Target],
+        cycles: 1,
+        execute: |operands, _| Some(operands[0].target()),
+    },
+];
+
+// Checked at compile time, an instruction without cycles would never complete
+const _: () = {
+    let mut idx = 0;
+    while idx < OPCODES.len() {
+        assert!(
+            OPCODES[idx].cycles > 0,
+            "instructions take at least one cycle"
+        );
+        assert!(
+            OPCODES[idx].operands.len() <= MAX_OPERANDS,
+            "too many operands"
+        );
+        idx += 1;
+    }
+};
+
+fn opcode(mnemonic: &str) -> Option<usize> {
+    OPCODES.iter().position(|op| op.mnemonic == mnemonic)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Instruction {
+    // Index in `OPCODES`
+    opcode: usize,
+    // Padded with zero values past the operands of the opcode
+    operands: [Operand; MAX_OPERANDS],
+}
 
-                values
+impl Instruction {
+    fn new(mnemonic: &str, operands: &[Operand]) -> Option<Self> {
+        let opcode = opcode(mnemonic)?;
+        let kinds = OPCODES[opcode].operands;
+
+        if kinds.len() != operands.len()
+            || kinds
+                .iter()
+                .zip(operands)
+                .any(|(&kind, o)| o.kind() != kind)
+        {
+            return None;
+        }
+
+        let mut padded = [Operand::Value(0); MAX_OPERANDS];
+        padded[..operands.len()].copy_from_slice(operands);
+
+        Some(Instruction {
+            opcode,
+            operands: padded,
+        })
+    }
+
+    fn opcode(&self) -> &'static Opcode {
+        &OPCODES[self.opcode]
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        self.opcode().mnemonic
+    }
+
+    fn operands(&self) -> &[Operand] {
+        &self.operands[..self.opcode().operands.len()]
+    }
+
+    fn target(&self) -> Option<usize> {
+        self.operands()
+            .iter()
+            .find(|operand| operand.kind() == OperandKind::Target)
+            .map(|operand| operand.target())
+    }
+
+    fn cycles(&self) -> usize {
+        self.opcode().cycles
+    }
+
+    fn execute(&self, registers: &mut Registers) -> Option<usize> {
+        (self.opcode().execute)(self.operands(), registers)
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_instruction(s, 1, &HashMap::new()).map_err(|_| ())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for operand in self.operands() {
+            write!(f, " {operand}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum AsmError {
+    UnknownOpcode {
+        line: usize,
+        mnemonic: String,
+    },
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand {
+        line: usize,
+        operand: String,
+    },
+    UnknownLabel {
+        line: usize,
+        label: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { line, mnemonic } => {
+                write!(f, "line {line}: unknown opcode {mnemonic:?}")
+            }
+            Self::OperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: expected {expected} operands, found {found}"
+            ),
+            Self::InvalidOperand { line, operand } => {
+                write!(f, "line {line}: invalid operand {operand:?}")
             }
-            _ => {
-                panic!();
+            Self::UnknownLabel { line, label } => write!(f, "line {line}: unknown label {label:?}"),
+            Self::DuplicateLabel { line, label } => {
+                write!(f, "line {line}: label {label:?} is already defined")
             }
+        }
+    }
+}
+
+fn parse_instruction(
+    text: &str,
+    line: usize,
+    labels: &HashMap<&str, usize>,
+) -> Result<Instruction, AsmError> {
+    let mut words = text.split_whitespace();
+    let mnemonic = words.next().unwrap_or_default();
+    let words: Vec<_> = words.collect();
+
+    let op = opcode(mnemonic)
+        .map(|idx| &OPCODES[idx])
+        .ok_or_else(|| AsmError::UnknownOpcode {
+            line,
+            mnemonic: mnemonic.to_string(),
+        })?;
+
+    if words.len() != op.operands.len() {
+        return Err(AsmError::OperandCount {
+            line,
+            expected: op.operands.len(),
+            found: words.len(),
+        });
+    }
+
+    let mut operands = vec![];
+    for (&word, &kind) in words.iter().zip(op.operands) {
+        let invalid = || AsmError::InvalidOperand {
+            line,
+            operand: word.to_string(),
         };
 
-        cycles_history.append(&mut values);
+        let operand = match kind {
+            OperandKind::Value => Operand::Value(word.parse().map_err(|_| invalid())?),
+            OperandKind::Register => Operand::Register(word.parse().map_err(|_| invalid())?),
+            OperandKind::Target => match (word.parse(), labels.get(word)) {
+                (Ok(address), _) => Operand::Target(address),
+                (_, Some(&address)) => Operand::Target(address),
+                _ if word.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                    return Err(AsmError::UnknownLabel {
+                        line,
+                        label: word.to_string(),
+                    })
+                }
+                _ => return Err(invalid()),
+            },
+        };
+        operands.push(operand);
     }
 
-    cycles_history
+    Ok(Instruction::new(mnemonic, &operands).unwrap())
 }
 
-fn solve_part1(input: &str) -> i32 {
-    const INTERESTING_CYCLES: &[i32] = &[20, 60, 100, 140, 180, 220];
+// Two passes, labels are `name:` either on their own line or before an
+// instruction, and comments run from `;` to the end of the line
+fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+
+    for (idx, l) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = l.split(';').next().unwrap().trim();
+
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(AsmError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+            text = rest.trim();
+        }
+
+        if !text.is_empty() {
+            lines.push((line, text));
+        }
+    }
 
-    gen_cycles(input)
+    lines
         .into_iter()
-        .enumerate()
-        .fold(0, |acc, (cycle, value)| {
-            let cycle = cycle as i32 + 1;
+        .map(|(line, text)| parse_instruction(text, line, &labels))
+        .collect()
+}
 
-            if INTERESTING_CYCLES.contains(&cycle) {
-                acc + cycle * value
-            } else {
-                acc
+// One line per instruction with its address and the cycles it runs during,
+// counted as if no jump was taken, jump targets get `L<address>` labels except
+// past the end of the program where only the address right after the last
+// instruction can be labelled
+fn disassemble(program: &[Instruction]) -> String {
+    let targets: HashSet<_> = program.iter().filter_map(|i| i.target()).collect();
+    let mut lines = vec![];
+    let mut cycle = 1;
+
+    for (address, &instruction) in program.iter().enumerate() {
+        if targets.contains(&address) {
+            lines.push(format!("L{address}:"));
+        }
+
+        let mut text = instruction.mnemonic().to_string();
+        for &operand in instruction.operands() {
+            match operand {
+                Operand::Target(target) if target <= program.len() => {
+                    text += &format!(" L{target}")
+                }
+                _ => text += &format!(" {operand}"),
             }
-        })
+        }
+
+        let cycles = instruction.cycles();
+        let during = if cycles == 1 {
+            format!("cycle {cycle}")
+        } else {
+            format!("cycles {cycle}-{}", cycle + cycles - 1)
+        };
+        lines.push(format!("    {text:<16}; {address:>4}: {during}"));
+
+        cycle += cycles;
+    }
+
+    if targets.contains(&program.len()) {
+        lines.push(format!("L{}:", program.len()));
+    }
+
+    lines.join("\n")
+}
+
+// Register state while a cycle is running and once it has completed, an
+// instruction only takes effect at the end of its last cycle
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Tick {
+    cycle: usize,
+    during: Registers,
+    after: Registers,
+}
+
+type Hook<'a> = Box<dyn FnMut(&Tick) + 'a>;
+
+struct Cpu<'a> {
+    program: Vec<Instruction>,
+    registers: Registers,
+    pc: usize,
+    cycle: usize,
+    // Cycles already spent on the instruction at `pc`
+    elapsed: usize,
+    hooks: Vec<Hook<'a>>,
+}
+
+impl<'a> Cpu<'a> {
+    fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            program,
+            registers: Registers::default(),
+            pc: 0,
+            cycle: 0,
+            elapsed: 0,
+            hooks: vec![],
+        }
+    }
+
+    fn on_cycle(&mut self, hook: impl FnMut(&Tick) + 'a) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    // Advances by a single cycle, returns `None` once the program has ended
+    fn step(&mut self) -> Option<Tick> {
+        let instruction = *self.program.get(self.pc)?;
+        let during = self.registers;
+
+        self.cycle += 1;
+        self.elapsed += 1;
+
+        if self.elapsed == instruction.cycles() {
+            let next = instruction.execute(&mut self.registers);
+            self.pc = next.unwrap_or(self.pc + 1);
+            self.elapsed = 0;
+        }
+
+        let tick = Tick {
+            cycle: self.cycle,
+            during,
+            after: self.registers,
+        };
+
+        for hook in &mut self.hooks {
+            hook(&tick);
+        }
+
+        Some(tick)
+    }
+
+    fn run(&mut self) {
+        while self.step().is_some() {}
+    }
+}
+
+fn parse(input: &str) -> Vec<Instruction> {
+    assemble(input).unwrap()
+}
+
+fn solve_part1(input: &str) -> i32 {
+    const INTERESTING_CYCLES: &[usize] = &[20, 60, 100, 140, 180, 220];
+    let mut strength = 0;
+
+    {
+        let mut cpu = Cpu::new(parse(input));
+        cpu.on_cycle(|tick| {
+            if INTERESTING_CYCLES.contains(&tick.cycle) {
+                strength += tick.cycle as i32 * tick.during.x;
+            }
+        });
+        cpu.run();
+    }
+
+    strength
 }
 
 fn solve_part2(input: &str) -> String {
     const LINE_LEN: usize = 40;
-    let cycles_history = gen_cycles(input);
+    let mut lines = vec![];
 
-    let mut out = String::new();
+    {
+        let mut cpu = Cpu::new(parse(input));
+        cpu.on_cycle(|tick| {
+            let position = (tick.cycle - 1) % LINE_LEN;
+            if position == 0 {
+                lines.push(String::new());
+            }
 
-    for (position, &x) in cycles_history.iter().enumerate() {
-        if (x - 1..=x + 1).contains(&((position % LINE_LEN) as i32)) {
-            out += "#";
-        } else {
-            out += ".";
+            let x = tick.during.x;
+            let pixel = if (x - 1..=x + 1).contains(&(position as i32)) {
+                '#'
+            } else {
+                '.'
+            };
+            lines.last_mut().unwrap().push(pixel);
+        });
+        cpu.run();
+    }
+
+    lines.join("\n")
+}
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+
+// Letters of the 4x6 font drawn by the CRT, each followed by a blank column
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[derive(Debug, Eq, PartialEq)]
+enum OcrError {
+    Shape { rows: usize },
+    Unrecognised(Vec<usize>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::Shape { rows } => write!(
+                f,
+                "expected {GLYPH_HEIGHT} rows of equal width, got {rows} rows"
+            ),
+            OcrError::Unrecognised(positions) => {
+                let positions: Vec<_> = positions
+                    .iter()
+                    .map(|&idx| {
+                        let col = idx * (GLYPH_WIDTH + 1);
+                        format!("#{idx} (columns {col}..{})", col + GLYPH_WIDTH)
+                    })
+                    .collect();
+                write!(f, "unrecognised glyphs at {}", positions.join(", "))
+            }
         }
+    }
+}
+
+fn recognise(screen: &str) -> Result<String, OcrError> {
+    let rows: Vec<_> = screen.lines().map(str::as_bytes).collect();
+    let width = rows.first().map_or(0, |row| row.len());
+
+    if rows.len() != GLYPH_HEIGHT || rows.iter().any(|row| row.len() != width) {
+        return Err(OcrError::Shape { rows: rows.len() });
+    }
+
+    let mut text = String::new();
+    let mut unrecognised = vec![];
 
-        if position != cycles_history.len() - 1 && (position + 1) % LINE_LEN == 0 {
-            out += "\n";
+    for (idx, col) in (0..width).step_by(GLYPH_WIDTH + 1).enumerate() {
+        let glyph = GLYPHS.iter().find(|(_, pattern)| {
+            rows.iter()
+                .zip(pattern)
+                .all(|(row, line)| row.get(col..col + GLYPH_WIDTH) == Some(line.as_bytes()))
+        });
+
+        match glyph {
+            Some(&(letter, _)) => text.push(letter),
+            None => unrecognised.push(idx),
         }
     }
 
-    out
+    if unrecognised.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognised(unrecognised))
+    }
 }
 
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
-    println!("Part 2:\n{}", solve_part2(INPUT));
+
+    let screen = solve_part2(INPUT);
+    match recognise(&screen) {
+        Ok(text) => println!("Part 2: {text}"),
+        Err(e) => println!("Part 2 ({e}):\n{screen}"),
+    }
+
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        ["--disassemble"] => println!("{}", disassemble(&parse(INPUT))),
+        ["--run", path] => match assemble(&fs::read_to_string(path).unwrap()) {
+            Ok(program) => {
+                let mut cpu = Cpu::new(program);
+                cpu.run();
+                println!("Halted after {} cycles with {:?}", cpu.cycle, cpu.registers);
+            }
+            Err(e) => println!("{path}: {e}"),
+        },
+        _ => {}
+    }
+
+    if args.contains(&"--trace") {
+        let mut cpu = Cpu::new(parse(INPUT));
+        cpu.on_cycle(|tick| {
+            println!(
+                "cycle {:>3}: during X={}, after X={}",
+                tick.cycle, tick.during.x, tick.after.x
+            );
+        });
+        cpu.run();
+
+        println!("Halted after {} cycles with {:?}", cpu.cycle, cpu.registers);
+    }
 }
 
 #[cfg(test)]
@@ -106,6 +702,167 @@ mod tests {
         assert_eq!(solve_part2(INPUT), part2);
     }
 
+    #[test]
+    fn day10_ocr() {
+        assert_eq!(recognise(&solve_part2(INPUT)), Ok("EKRHEPUZ".to_string()));
+
+        let every_glyph: Vec<_> = (0..GLYPH_HEIGHT)
+            .map(|row| {
+                GLYPHS
+                    .iter()
+                    .map(|(_, pattern)| pattern[row])
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect();
+        assert_eq!(
+            recognise(&every_glyph.join("\n")),
+            Ok("ABCEFGHIJKLOPRSUZ".to_string())
+        );
+
+        let mut smudged = solve_part2(INPUT).into_bytes();
+        smudged[41 + 1] = b'#';
+        smudged[41 * 3 + 35] = b'#';
+        let error = recognise(std::str::from_utf8(&smudged).unwrap()).unwrap_err();
+        assert_eq!(error, OcrError::Unrecognised(vec![0, 7]));
+        assert_eq!(
+            error.to_string(),
+            "unrecognised glyphs at #0 (columns 0..4), #7 (columns 35..39)"
+        );
+
+        let error = recognise(&solve_part2(TEST_INPUT)).unwrap_err();
+        assert_eq!(error, OcrError::Unrecognised((0..8).collect()));
+
+        assert_eq!(recognise("####\n#..."), Err(OcrError::Shape { rows: 2 }));
+        assert_eq!(
+            recognise("#\n#\n#\n#\n#\n##"),
+            Err(OcrError::Shape { rows: 6 })
+        );
+    }
+
+    #[test]
+    fn day10_assembler() {
+        let source = "; multiplies 3 by 4 into x
+            addy 4        ; loop counter
+        again:
+            addx 3
+            addy -1
+            jnz y again
+        end: noop";
+
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            program.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["addy 4", "addx 3", "addy -1", "jnz y 1", "noop"]
+        );
+
+        let mut cpu = Cpu::new(program.clone());
+        cpu.run();
+        assert_eq!(cpu.registers, Registers { x: 13, y: 0 });
+        assert_eq!(cpu.cycle, 2 + 4 * (2 + 2 + 2) + 1);
+
+        let listing = disassemble(&program);
+        assert_eq!(
+            listing,
+            "    addy 4          ;    0: cycles 1-2
+L1:
+    addx 3          ;    1: cycles 3-4
+    addy -1         ;    2: cycles 5-6
+    jnz y L1        ;    3: cycles 7-8
+    noop            ;    4: cycle 9"
+        );
+        assert_eq!(assemble(&listing).unwrap(), program);
+
+        let program = parse(INPUT);
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+
+        assert_eq!(
+            disassemble(&program).lines().last(),
+            Some("    noop            ;  139: cycle 240")
+        );
+
+        // Jumping to the end of the program halts it
+        let program = assemble("addx 1\njmp end\naddx 5\nend:").unwrap();
+        assert_eq!(program[1].to_string(), "jmp 3");
+        assert_eq!(disassemble(&program).lines().last(), Some("L3:"));
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+
+        let program = assemble("jmp 7\nnoop").unwrap();
+        assert!(disassemble(&program).starts_with("    jmp 7 "));
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+
+        assert_eq!(
+            "jmp 7".parse::<Instruction>().map(|i| i.to_string()),
+            Ok("jmp 7".to_string())
+        );
+        assert_eq!(
+            assemble("noop\nmul 3"),
+            Err(AsmError::UnknownOpcode {
+                line: 2,
+                mnemonic: "mul".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("addx 1 2").unwrap_err().to_string(),
+            "line 1: expected 1 operands, found 2"
+        );
+        assert_eq!(
+            assemble("jnz z 0").unwrap_err().to_string(),
+            "line 1: invalid operand \"z\""
+        );
+        assert_eq!(
+            assemble("jmp nowhere").unwrap_err().to_string(),
+            "line 1: unknown label \"nowhere\""
+        );
+        assert_eq!(
+            assemble("a: noop\na: noop").unwrap_err().to_string(),
+            "line 2: label \"a\" is already defined"
+        );
+    }
+
+    #[test]
+    fn day10_cpu() {
+        let program = parse("noop\naddx 3\naddx -5");
+        assert_eq!(
+            program.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["noop", "addx 3", "addx -5"]
+        );
+        assert_eq!(program[1].cycles(), 2);
+        assert_eq!(
+            Instruction::new("addx", &[Operand::Value(3)]),
+            Some(program[1])
+        );
+        assert_eq!(Instruction::new("addx", &[Operand::Target(3)]), None);
+        assert_eq!(Instruction::new("noop", &[Operand::Value(0)]), None);
+        assert_eq!(Instruction::new("mul", &[]), None);
+        assert_eq!("addx".parse::<Instruction>(), Err(()));
+        assert_eq!("mul 3".parse::<Instruction>(), Err(()));
+
+        let mut seen = vec![];
+        let mut cpu = Cpu::new(program);
+        cpu.on_cycle(|tick| seen.push((tick.cycle, tick.during.x, tick.after.x)));
+
+        let tick = cpu.step().unwrap();
+        assert_eq!(tick.cycle, 1);
+        assert_eq!(tick.during, Registers { x: 1, y: 0 });
+        assert_eq!(tick.after, Registers { x: 1, y: 0 });
+
+        cpu.step();
+        assert_eq!(cpu.registers.x, 1);
+        assert_eq!(cpu.step().unwrap().after.x, 4);
+
+        cpu.run();
+        assert_eq!(cpu.cycle, 5);
+        assert_eq!(cpu.registers.x, -1);
+        assert_eq!(cpu.step(), None);
+        drop(cpu);
+
+        assert_eq!(
+            seen,
+            [(1, 1, 1), (2, 1, 1), (3, 1, 4), (4, 4, 4), (5, 4, -1)]
+        );
+    }
+
     const TEST_INPUT: &str = "addx 15
 addx -11
 addx 6
@@ -16,7 +16,7 @@ struct Monkey<'a> {
     test_false_target: usize,
 }
 
-fn parse(input: &str) -> Vec<Monkey> {
+fn parse(input: &str) -> Vec<Monkey<'_>> {
     input
         .split("\n\n")
         .map(|block| {
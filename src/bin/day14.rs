@@ -74,10 +74,10 @@ fn solve_part1(input: &str) -> usize {
         let (mut x, mut y) = (500, 0);
 
         loop {
-            if grid.get(&Position { x, y: y + 1 }).is_some() {
-                if grid.get(&Position { x: x - 1, y: y + 1 }).is_none() {
+            if grid.contains_key(&Position { x, y: y + 1 }) {
+                if !grid.contains_key(&Position { x: x - 1, y: y + 1 }) {
                     x -= 1;
-                } else if grid.get(&Position { x: x + 1, y: y + 1 }).is_none() {
+                } else if !grid.contains_key(&Position { x: x + 1, y: y + 1 }) {
                     x += 1;
                 } else {
                     grid.insert(Position { x, y }, Type::Sand);
@@ -108,7 +108,7 @@ fn solve_part2(input: &str) -> usize {
     };
 
     let source = Position { x: 500, y: 0 };
-    while grid.get(&source).is_none() {
+    while !grid.contains_key(&source) {
         let mut pos = source.clone();
 
         loop {
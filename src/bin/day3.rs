@@ -1,54 +1,268 @@
-use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 
 const INPUT: &str = include_str!("day3_input.txt");
 
-fn get_priority(c: char) -> u32 {
-    if c.is_uppercase() {
-        c as u32 - 'A' as u32 + 27
+const ITEM_COUNT: u32 = 52;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const EMPTY: Self = Self(0);
+    const ALL: Self = Self((1 << ITEM_COUNT) - 1);
+
+    const fn contains(self, priority: u32) -> bool {
+        self.0 & (1 << (priority - 1)) != 0
+    }
+
+    const fn intersection(self, o: Self) -> Self {
+        Self(self.0 & o.0)
+    }
+
+    const fn union(self, o: Self) -> Self {
+        Self(self.0 | o.0)
+    }
+
+    const fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    fn priorities(self) -> impl Iterator<Item = u32> {
+        (1..=ITEM_COUNT).filter(move |&p| self.contains(p))
+    }
+
+    fn single_priority(self) -> Option<u32> {
+        if self.len() == 1 {
+            self.priorities().next()
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for ItemSet {
+    type Err = Anomaly;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars().try_fold(Self::EMPTY, |set, c| {
+            let priority = get_priority(c).ok_or(Anomaly::InvalidItem(c))?;
+            Ok(set.union(Self(1 << (priority - 1))))
+        })
+    }
+}
+
+impl fmt::Display for ItemSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.priorities()
+            .try_for_each(|p| write!(f, "{}", get_item(p)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Anomaly {
+    InvalidItem(char),
+    OddLength(usize),
+    NoSharedItem,
+    SharedItems(ItemSet),
+    NoBadge,
+    Badges(ItemSet),
+    IncompleteGroup(usize),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Diagnostic {
+    line: usize,
+    anomaly: Anomaly,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line = self.line;
+
+        match self.anomaly {
+            Anomaly::InvalidItem(c) => write!(f, "line {line}: invalid item {c:?}"),
+            Anomaly::OddLength(len) => write!(f, "line {line}: odd rucksack length {len}"),
+            Anomaly::NoSharedItem => write!(f, "line {line}: compartments share no item"),
+            Anomaly::SharedItems(items) => {
+                write!(
+                    f,
+                    "line {line}: compartments share {} items: {items}",
+                    items.len()
+                )
+            }
+            Anomaly::NoBadge => write!(f, "group at line {line}: no common badge"),
+            Anomaly::Badges(items) => {
+                write!(
+                    f,
+                    "group at line {line}: {} candidate badges: {items}",
+                    items.len()
+                )
+            }
+            Anomaly::IncompleteGroup(len) => {
+                write!(f, "group at line {line}: only {len} elves")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    Strict,
+    Lenient,
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    score: u32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    fn record(&mut self, line: usize, result: Result<u32, Anomaly>) {
+        match result {
+            Ok(priority) => self.score += priority,
+            Err(anomaly) => self.diagnostics.push(Diagnostic { line, anomaly }),
+        }
+    }
+
+    fn into_score(self, mode: Mode) -> Result<u32, Vec<Diagnostic>> {
+        if mode == Mode::Strict && !self.diagnostics.is_empty() {
+            Err(self.diagnostics)
+        } else {
+            Ok(self.score)
+        }
+    }
+}
+
+fn get_priority(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(c as u32 - 'A' as u32 + 27),
+        _ => None,
+    }
+}
+
+fn get_item(priority: u32) -> char {
+    if priority > 26 {
+        char::from(b'A' + (priority - 27) as u8)
     } else {
-        c as u32 - 'a' as u32 + 1
+        char::from(b'a' + (priority - 1) as u8)
     }
 }
 
-fn solve_part1(input: &str) -> u32 {
-    input
-        .lines()
-        .map(|l| {
-            let first: HashSet<_> = l[0..l.len() / 2].chars().collect();
-            let second: HashSet<_> = l[l.len() / 2..].chars().collect();
+fn common_items<I>(group: I) -> ItemSet
+where
+    I: IntoIterator<Item = ItemSet>,
+{
+    group.into_iter().fold(ItemSet::ALL, ItemSet::intersection)
+}
 
-            let intersection: Vec<_> = first.intersection(&second).copied().collect();
-            assert_eq!(intersection.len(), 1);
+fn check_rucksack(l: &str) -> Result<u32, Anomaly> {
+    l.parse::<ItemSet>()?;
 
-            get_priority(intersection[0])
-        })
-        .sum()
+    if !l.len().is_multiple_of(2) {
+        return Err(Anomaly::OddLength(l.len()));
+    }
+
+    let (first, second) = l.split_at(l.len() / 2);
+    let shared = common_items([first.parse()?, second.parse()?]);
+
+    if shared.is_empty() {
+        Err(Anomaly::NoSharedItem)
+    } else {
+        shared.single_priority().ok_or(Anomaly::SharedItems(shared))
+    }
 }
 
-fn solve_part2(input: &str) -> u32 {
+fn check_rucksacks(input: &str) -> Report {
+    let mut report = Report::default();
+
+    for (idx, l) in input.lines().enumerate() {
+        report.record(idx + 1, check_rucksack(l));
+    }
+
+    report
+}
+
+fn check_groups(input: &str, k: usize) -> Report {
+    assert!(k > 0, "groups need at least one elf");
+
     let lines: Vec<_> = input.lines().collect();
-    let mut sum = 0;
+    let mut report = Report::default();
 
-    for i in 0..lines.len() / 3 {
-        let elves: Vec<HashSet<_>> = lines[i * 3..(i + 1) * 3]
-            .iter()
-            .map(|&elve| elve.chars().collect())
-            .collect();
-        assert_eq!(elves.len(), 3);
+    for (idx, group) in lines.chunks(k).enumerate() {
+        let line = idx * k + 1;
 
-        let intersection: HashSet<_> = elves[0].intersection(&elves[1]).copied().collect();
-        let intersection: Vec<_> = intersection.intersection(&elves[2]).copied().collect();
-        assert_eq!(intersection.len(), 1);
+        if group.len() < k {
+            report.record(line, Err(Anomaly::IncompleteGroup(group.len())));
+            continue;
+        }
 
-        sum += get_priority(intersection[0]);
+        let mut elves = vec![];
+        for (offset, l) in group.iter().enumerate() {
+            match l.parse() {
+                Ok(items) => elves.push(items),
+                Err(anomaly) => report.record(line + offset, Err(anomaly)),
+            }
+        }
+
+        if elves.len() != k {
+            continue;
+        }
+
+        let badges = common_items(elves);
+        let result = if badges.is_empty() {
+            Err(Anomaly::NoBadge)
+        } else {
+            badges.single_priority().ok_or(Anomaly::Badges(badges))
+        };
+
+        report.record(line, result);
     }
 
-    sum
+    report
+}
+
+fn validate(input: &str, k: usize) -> Vec<Diagnostic> {
+    let mut diagnostics = check_rucksacks(input).diagnostics;
+    diagnostics.append(&mut check_groups(input, k).diagnostics);
+
+    diagnostics.sort_unstable();
+    diagnostics.dedup();
+    diagnostics
+}
+
+fn solve_part1(input: &str) -> u32 {
+    check_rucksacks(input).into_score(Mode::Strict).unwrap()
+}
+
+fn solve_part2(input: &str) -> u32 {
+    check_groups(input, 3).into_score(Mode::Strict).unwrap()
 }
 
 fn main() {
-    println!("Part 1: {}", solve_part1(INPUT));
-    println!("Part 2: {}", solve_part2(INPUT));
+    let diagnostics = validate(INPUT, 3);
+
+    if diagnostics.is_empty() {
+        println!("Part 1: {}", solve_part1(INPUT));
+        println!("Part 2: {}", solve_part2(INPUT));
+        return;
+    }
+
+    for diagnostic in diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    let part1 = check_rucksacks(INPUT).into_score(Mode::Lenient).unwrap();
+    let part2 = check_groups(INPUT, 3).into_score(Mode::Lenient).unwrap();
+
+    println!("Part 1 (lenient): {part1}");
+    println!("Part 2 (lenient): {part2}");
 }
 
 #[cfg(test)]
@@ -70,4 +284,71 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
         assert_eq!(solve_part2(TEST_INPUT), 70);
         assert_eq!(solve_part2(INPUT), 2499);
     }
+
+    #[test]
+    fn day3_item_set() {
+        let first: ItemSet = "vJrwpWtwJgWr".parse().unwrap();
+        let second: ItemSet = "hcsFMMfFFhFp".parse().unwrap();
+
+        assert_eq!(first.len(), 8);
+        assert!(!first.is_empty());
+        assert_eq!(first.intersection(second).to_string(), "p");
+        assert_eq!(first.union(second).len(), 8 + 7 - 1);
+        assert!(ItemSet::EMPTY.is_empty());
+        assert_eq!(ItemSet::ALL.len(), ITEM_COUNT);
+        assert_eq!("a1".parse::<ItemSet>(), Err(Anomaly::InvalidItem('1')));
+
+        let elves: Vec<ItemSet> = TEST_INPUT.lines().map(|l| l.parse().unwrap()).collect();
+        let pairs: Vec<_> = elves
+            .chunks(2)
+            .map(|group| common_items(group.iter().copied()).len())
+            .collect();
+        assert_eq!(pairs, [5, 5, 3]);
+        assert_eq!(common_items(elves.iter().copied()).len(), 0);
+
+        assert_eq!(check_groups(TEST_INPUT, 2).diagnostics.len(), 3);
+        assert_eq!(check_groups(TEST_INPUT, 6).diagnostics.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "groups need at least one elf")]
+    fn day3_empty_groups() {
+        check_groups(TEST_INPUT, 0);
+    }
+
+    #[test]
+    fn day3_diagnostics() {
+        const INVALID_INPUT: &str = "vJrwpWtwJgWrhcsFMMfFFhFp
+abcabc
+PmmdzqPrVvPwwTWBwg
+abc
+ab-ab
+CrZsJsPPZsGzwwsLwLmpwMDw
+abcd";
+
+        let diagnostics: Vec<_> = validate(INVALID_INPUT, 3)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            [
+                "group at line 1: no common badge",
+                "line 2: compartments share 3 items: abc",
+                "line 4: odd rucksack length 3",
+                "line 5: invalid item '-'",
+                "line 7: compartments share no item",
+                "group at line 7: only 1 elves",
+            ]
+        );
+
+        assert_eq!(
+            check_rucksacks(INVALID_INPUT).into_score(Mode::Lenient),
+            Ok(16 + 42 + 19)
+        );
+        assert!(check_groups(INVALID_INPUT, 3)
+            .into_score(Mode::Strict)
+            .is_err());
+    }
 }
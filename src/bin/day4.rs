@@ -50,6 +50,102 @@ fn parse_input(input: &str) -> Vec<(Range, Range)> {
         .collect()
 }
 
+fn roster(input: &str) -> Vec<Range> {
+    parse_input(input)
+        .into_iter()
+        .flat_map(|(first, second)| [first, second])
+        .collect()
+}
+
+#[derive(Debug)]
+struct Coverage {
+    segments: Vec<(Range, usize)>,
+}
+
+impl Coverage {
+    fn new(ranges: &[Range]) -> Self {
+        let mut events: Vec<(u32, isize)> = ranges
+            .iter()
+            .flat_map(|r| [(r.begin, 1), (r.end + 1, -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut segments = vec![];
+        let mut count = 0;
+
+        for (idx, &(position, delta)) in events.iter().enumerate() {
+            count += delta;
+
+            let Some(&(next, _)) = events.get(idx + 1) else {
+                break;
+            };
+
+            if next > position {
+                let range = Range {
+                    begin: position,
+                    end: next - 1,
+                };
+                segments.push((range, count as usize));
+            }
+        }
+
+        Self { segments }
+    }
+
+    fn matching<F>(&self, f: F) -> Vec<Range>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut ranges: Vec<Range> = vec![];
+
+        for &(range, count) in &self.segments {
+            if !f(count) {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(last) if last.end + 1 == range.begin => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+
+        ranges
+    }
+
+    fn uncovered(&self) -> Vec<Range> {
+        self.matching(|count| count == 0)
+    }
+
+    fn covered_more_than(&self, k: usize) -> Vec<Range> {
+        self.matching(|count| count > k)
+    }
+
+    fn max_overlap(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|&(_, count)| count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_redundant(&self, r: Range) -> bool {
+        let start = self
+            .segments
+            .partition_point(|(segment, _)| segment.end < r.begin);
+
+        self.segments[start..]
+            .iter()
+            .take_while(|(segment, _)| segment.begin <= r.end)
+            .all(|&(_, count)| count >= 2)
+    }
+
+    fn redundant_elves(&self, ranges: &[Range]) -> Vec<usize> {
+        (0..ranges.len())
+            .filter(|&idx| self.is_redundant(ranges[idx]))
+            .collect()
+    }
+}
+
 fn find_assignments<F>(input: &str, f: F) -> u32
 where
     F: Fn(Range, Range) -> bool,
@@ -72,6 +168,20 @@ fn solve_part2(input: &str) -> u32 {
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    let elves = roster(INPUT);
+    let coverage = Coverage::new(&elves);
+
+    println!("Uncovered ranges: {}", coverage.uncovered().len());
+    println!(
+        "Ranges covered by more than 2 elves: {}",
+        coverage.covered_more_than(2).len()
+    );
+    println!(
+        "Redundant elves: {}",
+        coverage.redundant_elves(&elves).len()
+    );
+    println!("Max overlap: {}", coverage.max_overlap());
 }
 
 #[cfg(test)]
@@ -93,4 +203,36 @@ mod tests {
         assert_eq!(solve_part2(TEST_INPUT), 4);
         assert_eq!(solve_part2(INPUT), 798);
     }
+
+    #[test]
+    fn day4_coverage() {
+        let elves = roster(TEST_INPUT);
+        let coverage = Coverage::new(&elves);
+
+        assert_eq!(coverage.max_overlap(), 8);
+        assert!(coverage.uncovered().is_empty());
+
+        let crowded: Vec<_> = coverage
+            .covered_more_than(5)
+            .into_iter()
+            .map(|r| (r.begin, r.end))
+            .collect();
+        assert_eq!(crowded, [(4, 7)]);
+
+        assert_eq!(
+            coverage.redundant_elves(&elves),
+            [0, 1, 2, 3, 4, 6, 7, 8, 9, 10, 11]
+        );
+
+        let sparse = roster("1-2,4-4\n8-9,4-5");
+        let coverage = Coverage::new(&sparse);
+        let gaps: Vec<_> = coverage
+            .uncovered()
+            .into_iter()
+            .map(|r| (r.begin, r.end))
+            .collect();
+
+        assert_eq!(gaps, [(3, 3), (6, 7)]);
+        assert_eq!(coverage.redundant_elves(&sparse), [1]);
+    }
 }
@@ -11,9 +11,7 @@ where
         .map(|l| {
             let split = |s: &str| -> HashSet<u32> {
                 let (begin, end) = s.split_once('-').unwrap();
-                (begin.parse().unwrap()..=end.parse().unwrap())
-                    .into_iter()
-                    .collect()
+                (begin.parse().unwrap()..=end.parse().unwrap()).collect()
             };
 
             let (first, second) = l.split_once(',').unwrap();
@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
 
 const INPUT: &str = include_str!("day5_input.txt");
 
@@ -7,96 +8,317 @@ enum ParseStep {
     Instructions,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 struct Instruction {
     count: usize,
     from: usize,
     to: usize,
 }
 
-fn parse(input: &str) -> (Vec<Vec<char>>, Vec<Instruction>) {
+impl FromStr for Instruction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().strip_prefix("move ").ok_or(())?;
+        let (count, s) = s.split_once(" from ").ok_or(())?;
+        let (from, to) = s.split_once(" to ").ok_or(())?;
+
+        Ok(Self {
+            count: count.trim().parse().map_err(|_| ())?,
+            from: from.trim().parse().map_err(|_| ())?,
+            to: to.trim().parse().map_err(|_| ())?,
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum MoveError {
+    UnknownStack {
+        step: usize,
+        instr: Instruction,
+        stack: usize,
+    },
+    Underflow {
+        step: usize,
+        instr: Instruction,
+        stack: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownStack { step, instr, stack } => {
+                write!(
+                    f,
+                    "instruction {step} ({instr}): stack {stack} does not exist"
+                )
+            }
+            Self::Underflow {
+                step,
+                instr,
+                stack,
+                available,
+            } => write!(
+                f,
+                "instruction {step} ({instr}): stack {stack} only holds {available} crates"
+            ),
+        }
+    }
+}
+
+type Stacks = Vec<Vec<char>>;
+
+trait Crane {
+    fn capacity(&self) -> usize;
+
+    fn apply(&self, stacks: &mut Stacks, instr: &Instruction) {
+        let mut remaining = instr.count;
+
+        while remaining > 0 {
+            let grabbed = remaining.min(self.capacity());
+            transfer(stacks, instr.from, instr.to, grabbed);
+            remaining -= grabbed;
+        }
+    }
+
+    fn undo(&self, stacks: &mut Stacks, instr: &Instruction) {
+        let mut remaining = instr.count;
+
+        // Grabs are undone last first, and only the last one can be partial
+        while remaining > 0 {
+            let grabbed = match remaining % self.capacity() {
+                0 => self.capacity(),
+                partial => partial,
+            };
+            transfer(stacks, instr.to, instr.from, grabbed);
+            remaining -= grabbed;
+        }
+    }
+}
+
+fn transfer(stacks: &mut Stacks, from: usize, to: usize, count: usize) {
+    let from = &mut stacks[from - 1];
+    let items: Vec<_> = from.drain(from.len() - count..).collect();
+    stacks[to - 1].extend(items);
+}
+
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn capacity(&self) -> usize {
+        1
+    }
+}
+
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn capacity(&self) -> usize {
+        usize::MAX
+    }
+}
+
+fn parse(input: &str) -> (Stacks, Vec<Instruction>) {
     let mut step = ParseStep::Crates;
-    let mut columns: Vec<Vec<char>> = vec![];
+    let mut stacks: Stacks = vec![];
     let mut instructions = vec![];
 
-    for l in input.lines() {
-        if l.is_empty() {
+    for (idx, l) in input.lines().enumerate() {
+        if l.trim().is_empty() {
             step = ParseStep::Instructions;
             continue;
         }
 
         match step {
             ParseStep::Crates => {
-                let blocks_count = l.len() / 4;
-
-                for i in 0..=blocks_count {
-                    let c = l.chars().nth(i * 4 + 1).unwrap();
-                    if c.is_ascii_digit() {
-                        break;
+                if l.trim_start().starts_with(|c: char| c.is_ascii_digit()) {
+                    let labels = l.split_whitespace().count();
+                    if stacks.len() < labels {
+                        stacks.resize(labels, vec![]);
                     }
+                    continue;
+                }
 
-                    if columns.len() < i + 1 {
-                        columns.push(vec![]);
-                    }
+                for (pos, _) in l.match_indices('[') {
+                    let idx = pos / 4;
+                    let c = l[pos + 1..].chars().next().unwrap();
 
-                    if c != ' ' {
-                        columns[i].push(c);
+                    if stacks.len() < idx + 1 {
+                        stacks.resize(idx + 1, vec![]);
                     }
+
+                    stacks[idx].push(c);
                 }
             }
             ParseStep::Instructions => {
-                let s: Vec<_> = l.split(' ').collect();
-                let count = s[1].parse().unwrap();
-                let from = s[3].parse().unwrap();
-                let to = s[5].parse().unwrap();
+                let instr = l
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid instruction on line {}: {l}", idx + 1));
 
-                instructions.push(Instruction { count, from, to });
+                instructions.push(instr);
             }
         }
     }
 
-    (columns, instructions)
+    for stack in &mut stacks {
+        stack.reverse();
+    }
+
+    (stacks, instructions)
 }
 
-fn solve_part1(input: &str) -> String {
-    let (columns, instructions) = parse(input);
+fn render(stacks: &Stacks) -> String {
+    let height = stacks.iter().map(Vec::len).max().unwrap_or(0);
+    let mut lines = vec![];
 
-    let mut columns: Vec<VecDeque<char>> = columns
-        .into_iter()
-        .map(|c| c.iter().copied().collect())
-        .collect();
+    for level in (0..height).rev() {
+        let cells: Vec<_> = stacks
+            .iter()
+            .map(|s| match s.get(level) {
+                Some(c) => format!("[{c}]"),
+                None => "   ".to_string(),
+            })
+            .collect();
 
-    for instr in instructions {
-        for _ in 0..instr.count {
-            let item = columns[instr.from - 1].pop_front().unwrap();
-            columns[instr.to - 1].push_front(item);
+        lines.push(cells.join(" "));
+    }
+
+    let footer: Vec<_> = (1..=stacks.len()).map(|i| format!("{i:^3}")).collect();
+    lines.push(footer.join(" "));
+
+    lines.join("\n")
+}
+
+fn check(
+    stacks: &Stacks,
+    step: usize,
+    instr: &Instruction,
+    source: usize,
+) -> Result<(), MoveError> {
+    for stack in [instr.from, instr.to] {
+        if !(1..=stacks.len()).contains(&stack) {
+            return Err(MoveError::UnknownStack {
+                step,
+                instr: *instr,
+                stack,
+            });
         }
     }
 
-    columns.into_iter().map(|c| *c.front().unwrap()).collect()
+    let available = stacks[source - 1].len();
+    if available < instr.count {
+        return Err(MoveError::Underflow {
+            step,
+            instr: *instr,
+            stack: source,
+            available,
+        });
+    }
+
+    Ok(())
 }
 
-fn solve_part2(input: &str) -> String {
-    let (mut columns, instructions) = parse(input);
+fn simulate<C, F>(
+    crane: &C,
+    stacks: &mut Stacks,
+    instructions: &[Instruction],
+    mut on_step: F,
+) -> Result<(), MoveError>
+where
+    C: Crane,
+    F: FnMut(&Instruction, &Stacks),
+{
+    for (idx, instr) in instructions.iter().enumerate() {
+        check(stacks, idx + 1, instr, instr.from)?;
+        crane.apply(stacks, instr);
+        on_step(instr, stacks);
+    }
+
+    Ok(())
+}
+
+fn run<C: Crane>(
+    crane: &C,
+    mut stacks: Stacks,
+    instructions: &[Instruction],
+) -> Result<Stacks, MoveError> {
+    simulate(crane, &mut stacks, instructions, |_, _| {})?;
+    Ok(stacks)
+}
 
-    for c in &mut columns {
-        c.reverse();
+fn trace<C: Crane>(
+    crane: &C,
+    mut stacks: Stacks,
+    instructions: &[Instruction],
+) -> Result<Vec<Stacks>, MoveError> {
+    let mut states = vec![];
+    simulate(crane, &mut stacks, instructions, |_, s| {
+        states.push(s.clone())
+    })?;
+    Ok(states)
+}
+
+fn unwind<C: Crane>(
+    crane: &C,
+    mut stacks: Stacks,
+    instructions: &[Instruction],
+) -> Result<Stacks, MoveError> {
+    for (idx, instr) in instructions.iter().enumerate().rev() {
+        check(&stacks, idx + 1, instr, instr.to)?;
+        crane.undo(&mut stacks, instr);
     }
 
-    for instr in instructions {
-        let column = &mut columns[instr.from - 1];
-        let mut items = column
-            .drain(column.len() - instr.count..column.len())
-            .collect();
-        columns[instr.to - 1].append(&mut items);
+    Ok(stacks)
+}
+
+fn tops(stacks: &Stacks) -> String {
+    stacks.iter().filter_map(|s| s.last()).collect()
+}
+
+fn solve<C: Crane>(input: &str, crane: &C) -> String {
+    let (stacks, instructions) = parse(input);
+    match run(crane, stacks, &instructions) {
+        Ok(stacks) => tops(&stacks),
+        Err(e) => panic!("{e}"),
     }
+}
 
-    columns.into_iter().map(|c| c[c.len() - 1]).collect()
+fn solve_part1(input: &str) -> String {
+    solve(input, &CrateMover9000)
+}
+
+fn solve_part2(input: &str) -> String {
+    solve(input, &CrateMover9001)
 }
 
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    if std::env::args().any(|arg| arg == "--trace") {
+        let (stacks, instructions) = parse(INPUT);
+        let states = trace(&CrateMover9001, stacks, &instructions).unwrap();
+
+        for (instr, stacks) in instructions.iter().zip(states) {
+            println!("\n{instr}\n{}", render(&stacks));
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--reverse") {
+        let (stacks, instructions) = parse(INPUT);
+        let stacks = run(&CrateMover9001, stacks, &instructions).unwrap();
+        let initial = unwind(&CrateMover9001, stacks, &instructions).unwrap();
+
+        println!("\nInitial arrangement:\n{}", render(&initial));
+    }
 }
 
 #[cfg(test)]
@@ -117,4 +339,116 @@ move 1 from 1 to 2";
         assert_eq!(solve_part2(TEST_INPUT), "MCD");
         assert_eq!(solve_part2(INPUT), "GGNPJBTTR");
     }
+
+    struct LimitedCrane(usize);
+
+    impl Crane for LimitedCrane {
+        fn capacity(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn day5_cranes() {
+        let (stacks, instructions) = parse(TEST_INPUT);
+
+        let states = trace(&CrateMover9000, stacks.clone(), &instructions).unwrap();
+        assert_eq!(states.len(), instructions.len());
+        assert_eq!(states[0], [vec!['Z', 'N', 'D'], vec!['M', 'C'], vec!['P']]);
+        assert_eq!(tops(states.last().unwrap()), "CMZ");
+
+        let limited = run(&LimitedCrane(1), stacks.clone(), &instructions).unwrap();
+        assert_eq!(tops(&limited), "CMZ");
+
+        let limited = run(&LimitedCrane(2), stacks, &instructions).unwrap();
+        assert_eq!(limited, [vec!['M'], vec!['C'], vec!['P', 'N', 'D', 'Z']]);
+    }
+
+    #[test]
+    fn day5_render() {
+        let (drawing, _) = TEST_INPUT.split_once("\n\n").unwrap();
+        let (stacks, _) = parse(TEST_INPUT);
+        assert_eq!(render(&stacks), drawing);
+
+        let (stacks, instructions) = parse(INPUT);
+        let mut states = trace(&CrateMover9000, stacks.clone(), &instructions).unwrap();
+        states.push(stacks);
+        states.push(vec![vec![]; 3]);
+
+        for stacks in states {
+            let (parsed, _) = parse(&render(&stacks));
+            assert_eq!(parsed, stacks);
+        }
+    }
+
+    #[test]
+    fn day5_parse() {
+        let stacks: Stacks = (0..12)
+            .map(|i| vec![char::from(b'A' + i); 1 + i as usize % 3])
+            .collect();
+        let (parsed, _) = parse(&render(&stacks));
+        assert_eq!(parsed, stacks);
+
+        const RAGGED_INPUT: &str = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3   4\n
+move 2 from 2 to 4
+move 1 from 4 to 10
+move 4 from 1 to 2";
+
+        let (stacks, instructions) = parse(RAGGED_INPUT);
+        assert_eq!(
+            stacks,
+            [vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P'], vec![]]
+        );
+
+        let instr = Instruction {
+            count: 1,
+            from: 4,
+            to: 10,
+        };
+        let err = run(&CrateMover9000, stacks.clone(), &instructions).unwrap_err();
+        assert_eq!(
+            err,
+            MoveError::UnknownStack {
+                step: 2,
+                instr,
+                stack: 10
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "instruction 2 (move 1 from 4 to 10): stack 10 does not exist"
+        );
+
+        let err = run(&CrateMover9001, stacks, &instructions[2..]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "instruction 1 (move 4 from 1 to 2): stack 1 only holds 2 crates"
+        );
+    }
+
+    #[test]
+    fn day5_unwind() {
+        fn check_unwind<C: Crane>(crane: &C, input: &str) {
+            let (initial, instructions) = parse(input);
+            let last = run(crane, initial.clone(), &instructions).unwrap();
+
+            let reversed = unwind(crane, last.clone(), &instructions).unwrap();
+            assert_eq!(reversed, initial);
+            assert_eq!(run(crane, reversed, &instructions).unwrap(), last);
+        }
+
+        check_unwind(&CrateMover9000, TEST_INPUT);
+        check_unwind(&CrateMover9000, INPUT);
+        check_unwind(&CrateMover9001, TEST_INPUT);
+        check_unwind(&CrateMover9001, INPUT);
+        check_unwind(&LimitedCrane(2), TEST_INPUT);
+        check_unwind(&LimitedCrane(4), INPUT);
+
+        let (stacks, instructions) = parse(TEST_INPUT);
+        let err = unwind(&CrateMover9000, stacks, &instructions).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "instruction 2 (move 3 from 1 to 3): stack 3 only holds 1 crates"
+        );
+    }
 }
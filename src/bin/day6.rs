@@ -1,29 +1,129 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::ops::Range;
 
 const INPUT: &str = include_str!("day6_input.txt");
 
-fn detect_sequence_start(input: &str, len: usize) -> usize {
-    for i in 0..input.len() - len - 1 {
-        let s: HashSet<_> = input[i..i + len].chars().collect();
-        if s.len() == len {
-            return i + len;
+struct Window {
+    len: usize,
+    bytes: VecDeque<u8>,
+    counts: [usize; 256],
+    distinct: usize,
+}
+
+impl Window {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            bytes: VecDeque::with_capacity(len + 1),
+            counts: [0; 256],
+            distinct: 0,
         }
     }
 
-    panic!();
+    fn push(&mut self, b: u8) -> bool {
+        self.bytes.push_back(b);
+        self.counts[b as usize] += 1;
+        if self.counts[b as usize] == 1 {
+            self.distinct += 1;
+        }
+
+        if self.bytes.len() > self.len {
+            let old = self.bytes.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 0 {
+                self.distinct -= 1;
+            }
+        }
+
+        self.bytes.len() == self.len && self.distinct == self.len
+    }
+}
+
+fn detect_sequence_start<I>(input: I, len: usize) -> Option<usize>
+where
+    I: IntoIterator<Item = u8>,
+{
+    markers(input, len).next()
+}
+
+fn markers<I>(input: I, len: usize) -> impl Iterator<Item = usize>
+where
+    I: IntoIterator<Item = u8>,
+{
+    let mut window = Window::new(len);
+
+    // An empty window is already complete before the first byte
+    let start = (len == 0).then_some(0);
+
+    start.into_iter().chain(
+        input
+            .into_iter()
+            .enumerate()
+            .filter_map(move |(idx, b)| window.push(b).then_some(idx + 1)),
+    )
+}
+
+fn longest_distinct_run(input: &[u8]) -> Range<usize> {
+    let (mut low, mut high) = (0, input.len().min(256));
+
+    while low < high {
+        let len = (low + high).div_ceil(2);
+        if detect_sequence_start(input.iter().copied(), len).is_some() {
+            low = len;
+        } else {
+            high = len - 1;
+        }
+    }
+
+    let end = detect_sequence_start(input.iter().copied(), low).unwrap();
+    end - low..end
+}
+
+fn split_messages(input: &[u8], len: usize) -> Vec<&[u8]> {
+    if len == 0 {
+        return vec![input];
+    }
+
+    let mut messages = vec![];
+    let mut start = None;
+    let mut pos = 0;
+
+    while let Some(end) = detect_sequence_start(input[pos..].iter().copied(), len) {
+        let end = pos + end;
+        if let Some(start) = start {
+            messages.push(&input[start..end - len]);
+        }
+
+        start = Some(end);
+        pos = end;
+    }
+
+    if let Some(start) = start {
+        messages.push(&input[start..]);
+    }
+
+    messages
 }
 
 fn solve_part1(input: &str) -> usize {
-    detect_sequence_start(input, 4)
+    detect_sequence_start(input.bytes(), 4).unwrap()
 }
 
 fn solve_part2(input: &str) -> usize {
-    detect_sequence_start(input, 14)
+    detect_sequence_start(input.bytes(), 14).unwrap()
 }
 
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    let bytes = INPUT.trim().as_bytes();
+    println!(
+        "Packet markers: {}",
+        markers(bytes.iter().copied(), 4).count()
+    );
+    println!("Longest distinct run: {:?}", longest_distinct_run(bytes));
+    println!("Messages: {}", split_messages(bytes, 14).len());
 }
 
 #[cfg(test)]
@@ -45,4 +145,41 @@ mod tests {
         assert_eq!(solve_part2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"), 26);
         assert_eq!(solve_part2(INPUT), 2383);
     }
+
+    #[test]
+    fn day6_detector() {
+        assert_eq!(detect_sequence_start("aabc".bytes(), 3), Some(4));
+        assert_eq!(detect_sequence_start("abc".bytes(), 3), Some(3));
+        assert_eq!(detect_sequence_start("ab".bytes(), 4), None);
+        assert_eq!(detect_sequence_start("aaaa".bytes(), 2), None);
+        assert_eq!(detect_sequence_start("".bytes(), 0), Some(0));
+        assert_eq!(detect_sequence_start([0, 255, 0, 1], 3), Some(4));
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(detect_sequence_start(bytes.iter().copied(), 256), Some(256));
+        assert_eq!(detect_sequence_start(bytes, 257), None);
+    }
+
+    #[test]
+    fn day6_analysis() {
+        let input = "abcabcdd";
+        assert_eq!(
+            markers(input.bytes(), 3).collect::<Vec<_>>(),
+            [3, 4, 5, 6, 7]
+        );
+        assert_eq!(markers(input.bytes(), 4).collect::<Vec<_>>(), [7]);
+        assert_eq!(markers("aaaa".bytes(), 2).count(), 0);
+        assert_eq!(markers("aab".bytes(), 0).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(detect_sequence_start("aab".bytes(), 0), Some(0));
+
+        assert_eq!(longest_distinct_run(input.as_bytes()), 3..7);
+        assert_eq!(longest_distinct_run(b"aaaa"), 0..1);
+        assert_eq!(longest_distinct_run(b""), 0..0);
+
+        let messages = split_messages(b"aaabcxxxxxdefyyy", 3);
+        assert_eq!(messages, [&b"xxxx"[..], b"fyyy"]);
+
+        let messages = split_messages(b"aaaa", 2);
+        assert!(messages.is_empty());
+    }
 }
@@ -1,145 +1,974 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
-use std::rc::{Rc, Weak};
-use std::vec;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 const INPUT: &str = include_str!("day7_input.txt");
 
-#[derive(Default, Debug)]
+const ROOT: usize = 0;
+
+const DISK_SIZE: u32 = 70000000;
+const REQUIRED_SPACE: u32 = 30000000;
+
+#[derive(Clone, Copy, Debug)]
+enum Node {
+    Dir(usize),
+    File(u32),
+}
+
+#[derive(Debug, Default)]
 struct Directory {
-    path: PathBuf,
-    parent: Option<Weak<RefCell<Directory>>>,
-    files_size: u32,
-    dir_size: u32,
-    dirs: HashMap<String, Rc<RefCell<Directory>>>,
+    name: String,
+    parent: Option<usize>,
+    entries: Vec<(String, Node)>,
 }
 
 impl Directory {
-    fn iter(d: Rc<RefCell<Self>>) -> DirectoryIter {
-        DirectoryIter { stack: vec![d] }
+    fn files_size(&self) -> u32 {
+        self.entries
+            .iter()
+            .map(|(_, node)| match node {
+                Node::File(size) => *size,
+                Node::Dir(_) => 0,
+            })
+            .sum()
     }
 }
 
-struct DirectoryIter {
-    stack: Vec<Rc<RefCell<Directory>>>,
+#[derive(Debug, Eq, PartialEq)]
+enum Entry<'a> {
+    Dir(&'a str),
+    File(&'a str, u32),
+}
+
+#[derive(Debug)]
+struct FileSystem {
+    dirs: Vec<Directory>,
 }
 
-impl Iterator for DirectoryIter {
-    type Item = Rc<RefCell<Directory>>;
+impl FileSystem {
+    fn new() -> Self {
+        Self {
+            dirs: vec![Directory::default()],
+        }
+    }
+
+    fn add_dir(&mut self, parent: usize, name: &str) -> usize {
+        if let Some(id) = self.child(parent, name) {
+            return id;
+        }
+
+        let id = self.dirs.len();
+        self.dirs.push(Directory {
+            name: name.into(),
+            parent: Some(parent),
+            ..Default::default()
+        });
+        self.dirs[parent].entries.push((name.into(), Node::Dir(id)));
+
+        id
+    }
+
+    fn add_file(&mut self, dir: usize, name: &str, size: u32) {
+        let entries = &mut self.dirs[dir].entries;
+
+        let existing = entries
+            .iter_mut()
+            .find(|(n, node)| n == name && matches!(node, Node::File(_)));
+
+        match existing {
+            Some((_, node)) => *node = Node::File(size),
+            None => entries.push((name.into(), Node::File(size))),
+        }
+    }
+
+    fn parent(&self, id: usize) -> Option<usize> {
+        self.dirs[id].parent
+    }
+
+    fn child(&self, id: usize, name: &str) -> Option<usize> {
+        self.dirs[id]
+            .entries
+            .iter()
+            .find_map(|(n, node)| match node {
+                Node::Dir(sub) if n == name => Some(*sub),
+                _ => None,
+            })
+    }
+
+    fn subdirs(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.dirs[id]
+            .entries
+            .iter()
+            .filter_map(|(_, node)| match node {
+                Node::Dir(sub) => Some(*sub),
+                Node::File(_) => None,
+            })
+    }
+
+    fn resolve(&self, cwd: usize, path: &str) -> Option<usize> {
+        let start = if path.starts_with('/') { ROOT } else { cwd };
+
+        path.split('/')
+            .filter(|&name| !name.is_empty() && name != ".")
+            .try_fold(start, |id, name| match name {
+                ".." => Some(self.parent(id).unwrap_or(ROOT)),
+                _ => self.child(id, name),
+            })
+    }
+
+    fn lookup(&self, path: &str) -> Option<usize> {
+        self.resolve(ROOT, path)
+    }
+
+    fn path(&self, id: usize) -> String {
+        match self.parent(id) {
+            None => "/".into(),
+            Some(ROOT) => format!("/{}", self.dirs[id].name),
+            Some(parent) => format!("{}/{}", self.path(parent), self.dirs[id].name),
+        }
+    }
+
+    fn list(&self, id: usize) -> Vec<Entry<'_>> {
+        self.dirs[id]
+            .entries
+            .iter()
+            .map(|(name, node)| match *node {
+                Node::Dir(_) => Entry::Dir(name),
+                Node::File(size) => Entry::File(name, size),
+            })
+            .collect()
+    }
+
+    fn total_size(&self, id: usize) -> u32 {
+        let dir = &self.dirs[id];
+
+        dir.files_size()
+            + dir
+                .entries
+                .iter()
+                .map(|(_, node)| match *node {
+                    Node::Dir(sub) => self.total_size(sub),
+                    Node::File(_) => 0,
+                })
+                .sum::<u32>()
+    }
+
+    fn sizes(&self) -> Vec<u32> {
+        let mut sizes: Vec<u32> = self.dirs.iter().map(Directory::files_size).collect();
+
+        // Children are always allocated after their parent
+        for id in (1..self.dirs.len()).rev() {
+            let parent = self.dirs[id].parent.unwrap();
+            sizes[parent] += sizes[id];
+        }
+
+        sizes
+    }
+
+    fn tree(&self) -> String {
+        let mut lines = vec![];
+        self.tree_lines(ROOT, 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn tree_lines(&self, id: usize, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        let name = if id == ROOT { "/" } else { &self.dirs[id].name };
+        lines.push(format!("{indent}- {name} (dir)"));
+
+        for (name, node) in &self.dirs[id].entries {
+            match *node {
+                Node::Dir(sub) => self.tree_lines(sub, depth + 1, lines),
+                Node::File(size) => {
+                    lines.push(format!("{indent}  - {name} (file, size={size})"));
+                }
+            }
+        }
+    }
+
+    fn du(&self) -> String {
+        let mut report: Vec<_> = self
+            .sizes()
+            .into_iter()
+            .enumerate()
+            .map(|(id, size)| (size, self.path(id)))
+            .collect();
+        report.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        report
+            .into_iter()
+            .map(|(size, path)| format!("{}\t{path}", human_size(size)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn entry_path(&self, dir: usize, name: &str) -> String {
+        match dir {
+            ROOT => format!("/{name}"),
+            _ => format!("{}/{name}", self.path(dir)),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut lines = vec![];
+        self.json_lines(ROOT, "/", 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn json_lines(&self, id: usize, name: &str, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        let entries = &self.dirs[id].entries;
+
+        lines.push(format!("{indent}{{"));
+        lines.push(format!("{indent}  \"name\": {},", json_string(name)));
+        lines.push(format!("{indent}  \"type\": \"dir\","));
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let Some(d) = self.stack.pop() else {
-            return None;
+        if entries.is_empty() {
+            lines.push(format!("{indent}  \"entries\": []"));
+        } else {
+            lines.push(format!("{indent}  \"entries\": ["));
+
+            for (idx, (name, node)) in entries.iter().enumerate() {
+                match *node {
+                    Node::Dir(sub) => self.json_lines(sub, name, depth + 2, lines),
+                    Node::File(size) => {
+                        lines.push(format!("{indent}    {{"));
+                        lines.push(format!("{indent}      \"name\": {},", json_string(name)));
+                        lines.push(format!("{indent}      \"type\": \"file\","));
+                        lines.push(format!("{indent}      \"size\": {size}"));
+                        lines.push(format!("{indent}    }}"));
+                    }
+                }
+
+                if idx + 1 < entries.len() {
+                    lines.last_mut().unwrap().push(',');
+                }
+            }
+
+            lines.push(format!("{indent}  ]"));
+        }
+
+        lines.push(format!("{indent}}}"));
+    }
+
+    fn transcript(&self) -> String {
+        let mut lines = vec![];
+        let mut stack = vec![ROOT];
+
+        while let Some(id) = stack.pop() {
+            let entries = &self.dirs[id].entries;
+            if entries.is_empty() {
+                continue;
+            }
+
+            lines.push(format!("$ cd {}", self.path(id)));
+            lines.push("$ ls".into());
+
+            for (name, node) in entries {
+                match node {
+                    Node::Dir(_) => lines.push(format!("dir {name}")),
+                    Node::File(size) => lines.push(format!("{size} {name}")),
+                }
+            }
+
+            let subdirs: Vec<_> = self.subdirs(id).collect();
+            stack.extend(subdirs.into_iter().rev());
+        }
+
+        lines.join("\n")
+    }
+
+    fn diff(&self, other: &Self) -> Vec<String> {
+        let mut changes = vec![];
+        self.diff_dir(ROOT, other, ROOT, &mut changes);
+        changes
+    }
+
+    fn diff_dir(&self, id: usize, other: &Self, other_id: usize, changes: &mut Vec<String>) {
+        let find = |entries: &[(String, Node)], name: &str, node: Node| {
+            entries
+                .iter()
+                .find(|(n, o)| {
+                    n == name
+                        && matches!(
+                            (node, o),
+                            (Node::Dir(_), Node::Dir(_)) | (Node::File(_), Node::File(_))
+                        )
+                })
+                .map(|&(_, o)| o)
         };
 
-        self.stack
-            .append(&mut d.borrow().dirs.values().cloned().collect());
+        let describe = |fs: &Self, dir: usize, name: &str, node: Node| match node {
+            Node::Dir(_) => format!("{} (dir)", fs.entry_path(dir, name)),
+            Node::File(size) => format!("{} (file, size={size})", fs.entry_path(dir, name)),
+        };
 
-        Some(d)
+        for (name, node) in &self.dirs[id].entries {
+            match (*node, find(&other.dirs[other_id].entries, name, *node)) {
+                (_, None) => changes.push(format!("- {}", describe(self, id, name, *node))),
+                (Node::Dir(sub), Some(Node::Dir(other_sub))) => {
+                    self.diff_dir(sub, other, other_sub, changes);
+                }
+                (Node::File(size), Some(Node::File(other_size))) if size != other_size => {
+                    changes.push(format!(
+                        "~ {} (size={size} -> size={other_size})",
+                        self.entry_path(id, name)
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for (name, node) in &other.dirs[other_id].entries {
+            if find(&self.dirs[id].entries, name, *node).is_none() {
+                changes.push(format!("+ {}", describe(other, other_id, name, *node)));
+            }
+        }
+    }
+}
+
+impl PartialEq for FileSystem {
+    fn eq(&self, other: &Self) -> bool {
+        self.diff(other).is_empty()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
     }
+
+    out.push('"');
+    out
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Strategy {
+    SmallestDirectory,
+    FewestDirectories,
+    MinimalBytes,
 }
 
-fn fill_directory_size(root: &Rc<RefCell<Directory>>) -> u32 {
-    let mut root = root.borrow_mut();
-    root.dir_size = root.files_size;
+impl Strategy {
+    const fn key(self, count: usize, freed: u32) -> (u64, u64) {
+        match self {
+            Self::SmallestDirectory | Self::FewestDirectories => (count as u64, freed as u64),
+            // Breaking ties on the directory count makes the search blow up
+            Self::MinimalBytes => (freed as u64, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Plan {
+    strategy: Strategy,
+    disk_size: u32,
+    required: u32,
+    used: u32,
+    dirs: Vec<usize>,
+    freed: u32,
+}
 
-    let dirs: Vec<_> = root.dirs.values().cloned().collect();
-    for sub in dirs {
-        root.dir_size += fill_directory_size(&sub);
+impl Plan {
+    fn available(&self) -> u32 {
+        self.disk_size.saturating_sub(self.used)
     }
 
-    root.dir_size
+    fn needed(&self) -> u32 {
+        self.required.saturating_sub(self.available())
+    }
+
+    fn explain(&self, fs: &FileSystem) -> String {
+        let sizes = fs.sizes();
+        let mut lines = vec![
+            format!("Strategy: {:?}", self.strategy),
+            format!(
+                "Disk size {}, used {}, available {}",
+                self.disk_size,
+                self.used,
+                self.available()
+            ),
+            format!(
+                "Required {} available, {} bytes to free",
+                self.required,
+                self.needed()
+            ),
+        ];
+
+        for &id in &self.dirs {
+            lines.push(format!("Delete {} ({} bytes)", fs.path(id), sizes[id]));
+        }
+
+        lines.push(format!(
+            "Frees {} bytes in {} directories, leaving {} available",
+            self.freed,
+            self.dirs.len(),
+            self.available() + self.freed
+        ));
+
+        lines.join("\n")
+    }
+}
+
+struct Planner<'a> {
+    fs: &'a FileSystem,
+    sizes: Vec<u32>,
+    needed: u32,
+    strategy: Strategy,
+    chosen: Vec<usize>,
+    best: Option<(Vec<usize>, u32)>,
+}
+
+impl Planner<'_> {
+    fn best_key(&self) -> Option<(u64, u64)> {
+        self.best
+            .as_ref()
+            .map(|(dirs, freed)| self.strategy.key(dirs.len(), *freed))
+    }
+
+    fn subdirs(&self, id: usize) -> Vec<usize> {
+        let mut subdirs: Vec<_> = self.fs.subdirs(id).collect();
+        subdirs.sort_unstable_by_key(|&sub| self.sizes[sub]);
+        subdirs
+    }
+
+    fn smallest_directory(&mut self) {
+        self.best = (0..self.sizes.len())
+            .filter(|&id| id != ROOT && self.sizes[id] >= self.needed)
+            .min_by_key(|&id| self.sizes[id])
+            .map(|id| (vec![id], self.sizes[id]));
+    }
+
+    // Branch and bound over sets of non-nested directories: the frontier holds the
+    // directories that can still be deleted, and each one is either deleted as a
+    // whole or replaced by its subdirectories.
+    fn search(&mut self, frontier: &mut Vec<usize>, freed: u32, potential: u32) {
+        if freed >= self.needed {
+            let key = self.strategy.key(self.chosen.len(), freed);
+            if self.best_key().is_none_or(|best| key < best) {
+                self.best = Some((self.chosen.clone(), freed));
+            }
+            return;
+        }
+
+        if freed + potential < self.needed {
+            return;
+        }
+
+        let mut remaining: Vec<_> = frontier.iter().map(|&id| self.sizes[id]).collect();
+        remaining.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut more = 0;
+        let mut reachable = freed;
+        while reachable < self.needed {
+            reachable += remaining[more];
+            more += 1;
+        }
+
+        let bound = self.strategy.key(self.chosen.len() + more, self.needed);
+        if self.best_key().is_some_and(|best| bound >= best) {
+            return;
+        }
+
+        let Some(id) = frontier.pop() else {
+            return;
+        };
+        let size = self.sizes[id];
+
+        self.chosen.push(id);
+        self.search(frontier, freed + size, potential - size);
+        self.chosen.pop();
+
+        let subdirs = self.subdirs(id);
+        let count = subdirs.len();
+        let files_size = size - subdirs.iter().map(|&sub| self.sizes[sub]).sum::<u32>();
+
+        frontier.extend(subdirs);
+        self.search(frontier, freed, potential - files_size);
+        frontier.truncate(frontier.len() - count);
+
+        frontier.push(id);
+    }
+}
+
+fn plan(fs: &FileSystem, disk_size: u32, required: u32, strategy: Strategy) -> Option<Plan> {
+    let sizes = fs.sizes();
+    let used = sizes[ROOT];
+
+    let mut plan = Plan {
+        strategy,
+        disk_size,
+        required,
+        used,
+        dirs: vec![],
+        freed: 0,
+    };
+
+    let mut planner = Planner {
+        fs,
+        sizes,
+        needed: plan.needed(),
+        strategy,
+        chosen: vec![],
+        best: None,
+    };
+
+    // The root directory itself can't be deleted, only its content
+    match strategy {
+        Strategy::SmallestDirectory if planner.needed > 0 => planner.smallest_directory(),
+        _ => {
+            let mut frontier = planner.subdirs(ROOT);
+            let potential = frontier.iter().map(|&id| planner.sizes[id]).sum();
+            planner.search(&mut frontier, 0, potential);
+        }
+    }
+
+    let (dirs, freed) = planner.best?;
+    plan.dirs = dirs;
+    plan.freed = freed;
+
+    Some(plan)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Dirs,
+    Files,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Field {
+    Size,
+    Name,
+    Path,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn test(self, ord: Ordering) -> bool {
+        match self {
+            Self::Lt => ord.is_lt(),
+            Self::Le => ord.is_le(),
+            Self::Gt => ord.is_gt(),
+            Self::Ge => ord.is_ge(),
+            Self::Eq => ord.is_eq(),
+            Self::Ne => ord.is_ne(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum QueryError {
+    UnexpectedEnd,
+    UnexpectedToken(String),
+    InvalidNumber(String),
+    NoSuchDirectory(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of query"),
+            Self::UnexpectedToken(token) => write!(f, "unexpected token {token:?}"),
+            Self::InvalidNumber(token) => write!(f, "invalid number {token:?}"),
+            Self::NoSuchDirectory(path) => write!(f, "no such directory {path:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct Row {
+    path: String,
+    name: String,
+    size: u32,
+}
+
+#[derive(Debug)]
+struct Query {
+    kind: Kind,
+    under: Option<String>,
+    pattern: Option<String>,
+    conditions: Vec<(Op, u32)>,
+    order: Option<(Field, bool)>,
+    limit: Option<usize>,
+}
+
+fn next_token<'a, I>(tokens: &mut I) -> Result<&'a str, QueryError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    tokens.next().ok_or(QueryError::UnexpectedEnd)
 }
 
-fn parse(input: &str) -> Rc<RefCell<Directory>> {
-    let root = Rc::new(RefCell::new(Directory {
-        path: Path::new("/").into(),
-        ..Default::default()
-    }));
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace().peekable();
+
+        let kind = match next_token(&mut tokens)? {
+            "dirs" => Kind::Dirs,
+            "files" => Kind::Files,
+            token => return Err(QueryError::UnexpectedToken(token.into())),
+        };
 
-    let mut cwd = root.clone();
+        let mut query = Self {
+            kind,
+            under: None,
+            pattern: None,
+            conditions: vec![],
+            order: None,
+            limit: None,
+        };
 
-    for l in input.lines() {
-        match &l[0..4] {
-            "$ cd" => {
-                let p = &l[5..];
+        let number = |token: &str| {
+            token
+                .parse()
+                .map_err(|_| QueryError::InvalidNumber(token.into()))
+        };
 
-                match p {
-                    "/" => {}
-                    ".." => {
-                        let parent = cwd.borrow().parent.as_ref().unwrap().upgrade().unwrap();
-                        cwd = parent;
+        while let Some(token) = tokens.next() {
+            match token {
+                "under" => query.under = Some(next_token(&mut tokens)?.into()),
+                "matching" => query.pattern = Some(next_token(&mut tokens)?.into()),
+                "where" | "and" => {
+                    match next_token(&mut tokens)? {
+                        "size" => {}
+                        token => return Err(QueryError::UnexpectedToken(token.into())),
                     }
-                    _ => {
-                        let subdir = cwd.borrow().dirs.get(p).unwrap().clone();
-                        cwd = subdir;
+
+                    let op = match next_token(&mut tokens)? {
+                        "<" => Op::Lt,
+                        "<=" => Op::Le,
+                        ">" => Op::Gt,
+                        ">=" => Op::Ge,
+                        "=" | "==" => Op::Eq,
+                        "!=" => Op::Ne,
+                        token => return Err(QueryError::UnexpectedToken(token.into())),
+                    };
+
+                    query
+                        .conditions
+                        .push((op, number(next_token(&mut tokens)?)?));
+                }
+                "order" => {
+                    match next_token(&mut tokens)? {
+                        "by" => {}
+                        token => return Err(QueryError::UnexpectedToken(token.into())),
                     }
+
+                    let field = match next_token(&mut tokens)? {
+                        "size" => Field::Size,
+                        "name" => Field::Name,
+                        "path" => Field::Path,
+                        token => return Err(QueryError::UnexpectedToken(token.into())),
+                    };
+
+                    let desc = tokens.next_if(|&t| t == "asc" || t == "desc") == Some("desc");
+                    query.order = Some((field, desc));
                 }
+                "limit" => query.limit = Some(number(next_token(&mut tokens)?)? as usize),
+                _ => return Err(QueryError::UnexpectedToken(token.into())),
             }
-            "$ ls" => {}
-            _ => {
-                let (first, name) = l.split_once(' ').unwrap();
+        }
 
-                if first == "dir" {
-                    let parent = Rc::downgrade(&cwd);
+        Ok(query)
+    }
+}
 
-                    let mut cwd = cwd.borrow_mut();
-                    let path = cwd.path.join(name);
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            glob_match(rest, name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name))) => glob_match(rest, name),
+        (Some((p, rest)), Some((n, name))) => p == n && glob_match(rest, name),
+        _ => false,
+    }
+}
 
-                    cwd.dirs.insert(
-                        name.into(),
-                        Rc::new(RefCell::new(Directory {
-                            path,
-                            parent: Some(parent),
-                            ..Default::default()
-                        })),
-                    );
+impl Query {
+    fn run(&self, fs: &FileSystem) -> Result<Vec<Row>, QueryError> {
+        let start = match &self.under {
+            Some(path) => fs
+                .lookup(path)
+                .ok_or_else(|| QueryError::NoSuchDirectory(path.clone()))?,
+            None => ROOT,
+        };
+
+        let sizes = fs.sizes();
+        let mut rows = vec![];
+        let mut stack = vec![start];
+
+        while let Some(id) = stack.pop() {
+            let path = fs.path(id);
+
+            match self.kind {
+                Kind::Dirs => rows.push(Row {
+                    name: if id == ROOT {
+                        "/".into()
+                    } else {
+                        fs.dirs[id].name.clone()
+                    },
+                    path,
+                    size: sizes[id],
+                }),
+                Kind::Files => {
+                    for entry in fs.list(id) {
+                        if let Entry::File(name, size) = entry {
+                            rows.push(Row {
+                                path: fs.entry_path(id, name),
+                                name: name.into(),
+                                size,
+                            });
+                        }
+                    }
+                }
+            }
+
+            let subdirs: Vec<_> = fs.subdirs(id).collect();
+            stack.extend(subdirs.into_iter().rev());
+        }
+
+        rows.retain(|row| {
+            let matching = match &self.pattern {
+                Some(pattern) => glob_match(pattern.as_bytes(), row.name.as_bytes()),
+                None => true,
+            };
+
+            matching
+                && self
+                    .conditions
+                    .iter()
+                    .all(|&(op, value)| op.test(row.size.cmp(&value)))
+        });
+
+        if let Some((field, desc)) = self.order {
+            rows.sort_by(|a, b| {
+                let ord = match field {
+                    Field::Size => a.size.cmp(&b.size),
+                    Field::Name => a.name.cmp(&b.name),
+                    Field::Path => a.path.cmp(&b.path),
+                };
+
+                if desc {
+                    ord.reverse()
                 } else {
-                    let mut cwd = cwd.borrow_mut();
-                    cwd.files_size += first.parse::<u32>().unwrap();
+                    ord
                 }
+            });
+        }
+
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+
+        Ok(rows)
+    }
+}
+
+impl FileSystem {
+    fn query(&self, query: &str) -> Result<Vec<Row>, QueryError> {
+        query.parse::<Query>()?.run(self)
+    }
+}
+
+fn human_size(size: u32) -> String {
+    const UNITS: &[&str] = &["K", "M", "G"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = f64::from(size);
+    let mut unit = "";
+
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+
+        value /= 1024.0;
+        unit = u;
+    }
+
+    if value < 10.0 {
+        format!("{:.1}{unit}", (value * 10.0).ceil() / 10.0)
+    } else {
+        format!("{}{unit}", value.ceil())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum ParseError {
+    UnknownCommand { line: usize, command: String },
+    NoSuchDirectory { line: usize, path: String },
+    UnexpectedOutput { line: usize },
+    InvalidOutput { line: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownCommand { line, command } => {
+                write!(f, "line {line}: unknown command {command:?}")
+            }
+            Self::NoSuchDirectory { line, path } => {
+                write!(f, "line {line}: no such directory {path:?}")
             }
+            Self::UnexpectedOutput { line } => {
+                write!(f, "line {line}: output without a preceding ls")
+            }
+            Self::InvalidOutput { line } => write!(f, "line {line}: invalid ls output"),
         }
     }
+}
+
+fn parse(input: &str) -> Result<FileSystem, ParseError> {
+    let mut fs = FileSystem::new();
+    let mut cwd = ROOT;
+    let mut listing = false;
 
-    fill_directory_size(&root);
+    for (idx, l) in input.lines().enumerate() {
+        let line = idx + 1;
 
-    root
+        if let Some(command) = l.strip_prefix('$') {
+            let command = command.trim();
+            listing = false;
+
+            match command.split_whitespace().collect::<Vec<_>>()[..] {
+                ["cd", path] => {
+                    cwd = fs
+                        .resolve(cwd, path)
+                        .ok_or_else(|| ParseError::NoSuchDirectory {
+                            line,
+                            path: path.into(),
+                        })?;
+                }
+                ["ls"] => listing = true,
+                _ => {
+                    return Err(ParseError::UnknownCommand {
+                        line,
+                        command: command.into(),
+                    })
+                }
+            }
+
+            continue;
+        }
+
+        if !listing {
+            return Err(ParseError::UnexpectedOutput { line });
+        }
+
+        let (first, name) = l
+            .split_once(' ')
+            .ok_or(ParseError::InvalidOutput { line })?;
+
+        if first == "dir" {
+            fs.add_dir(cwd, name);
+        } else {
+            let size = first
+                .parse()
+                .map_err(|_| ParseError::InvalidOutput { line })?;
+            fs.add_file(cwd, name, size);
+        }
+    }
+
+    Ok(fs)
 }
 
 fn solve_part1(input: &str) -> u32 {
-    let root = parse(input);
+    let fs = parse(input).unwrap();
 
-    Directory::iter(root)
-        .map(|d| d.borrow().dir_size)
-        .filter(|&s| s <= 100000)
+    fs.query("dirs where size <= 100000")
+        .unwrap()
+        .iter()
+        .map(|row| row.size)
         .sum()
 }
 
 fn solve_part2(input: &str) -> u32 {
-    let root = parse(input);
-    let to_be_freed = root.borrow().dir_size - 40000000;
+    let fs = parse(input).unwrap();
 
-    Directory::iter(root)
-        .map(|d| d.borrow().dir_size)
-        .filter(|&s| s >= to_be_freed)
-        .min()
+    plan(&fs, DISK_SIZE, REQUIRED_SPACE, Strategy::SmallestDirectory)
         .unwrap()
+        .freed
 }
 
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    let fs = parse(INPUT).unwrap();
+
+    let args: Vec<_> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("--tree") => println!("{}", fs.tree()),
+        Some("--du") => println!("{}", fs.du()),
+        Some("--json") => println!("{}", fs.to_json()),
+        Some("--transcript") => println!("{}", fs.transcript()),
+        Some("--query") => match fs.query(&args[1..].join(" ")) {
+            Ok(rows) => {
+                for row in rows {
+                    println!("{}\t{}", row.size, row.path);
+                }
+            }
+            Err(e) => eprintln!("Invalid query: {e}"),
+        },
+        Some("--plan") => {
+            for strategy in [
+                Strategy::SmallestDirectory,
+                Strategy::FewestDirectories,
+                Strategy::MinimalBytes,
+            ] {
+                match plan(&fs, DISK_SIZE, REQUIRED_SPACE, strategy) {
+                    Some(plan) => println!("{}\n", plan.explain(&fs)),
+                    None => println!("Strategy: {strategy:?}\nNo possible plan\n"),
+                }
+            }
+        }
+        Some(path) => {
+            let id = fs
+                .lookup(path)
+                .unwrap_or_else(|| panic!("No such directory {path}"));
+
+            println!("{} (size={})", fs.path(id), fs.total_size(id));
+            for entry in fs.list(id) {
+                match entry {
+                    Entry::Dir(name) => println!("dir {name}"),
+                    Entry::File(name, size) => println!("{size} {name}"),
+                }
+            }
+        }
+        None => {}
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn day7() {
-        const TEST_INPUT: &str = "$ cd /
+    const TEST_INPUT: &str = "$ cd /
 $ ls
 dir a
 14848514 b.txt
@@ -163,10 +992,316 @@ $ ls
 5626152 d.ext
 7214296 k";
 
+    #[test]
+    fn day7() {
         assert_eq!(solve_part1(TEST_INPUT), 95437);
         assert_eq!(solve_part1(INPUT), 1667443);
 
         assert_eq!(solve_part2(TEST_INPUT), 24933642);
         assert_eq!(solve_part2(INPUT), 8998590);
     }
+
+    #[test]
+    fn day7_filesystem() {
+        let fs = parse(TEST_INPUT).unwrap();
+
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.list(e), [Entry::File("i", 584)]);
+        assert_eq!(fs.total_size(e), 584);
+        assert_eq!(fs.lookup("a/e/"), Some(e));
+        assert_eq!(fs.lookup("/a/x"), None);
+
+        assert_eq!(
+            fs.list(ROOT),
+            [
+                Entry::Dir("a"),
+                Entry::File("b.txt", 14848514),
+                Entry::File("c.dat", 8504156),
+                Entry::Dir("d"),
+            ]
+        );
+
+        let sizes = fs.sizes();
+        for (id, &size) in sizes.iter().enumerate() {
+            assert_eq!(size, fs.total_size(id));
+        }
+        assert_eq!(fs.total_size(fs.lookup("/d").unwrap()), 24933642);
+        assert_eq!(fs.total_size(ROOT), 48381165);
+    }
+
+    #[test]
+    fn day7_interpreter() {
+        const SHELL_INPUT: &str = "$ ls
+dir a
+1 x
+$ cd a/
+$ ls
+dir b
+$ cd b
+$ ls
+10 y
+$ cd /
+$ ls
+dir a
+1 x
+$ cd /a/b/../b
+$ ls
+10 y
+$ cd ../../..
+$ cd ./a
+$ ls
+100 z";
+
+        let fs = parse(SHELL_INPUT).unwrap();
+        assert_eq!(fs.total_size(ROOT), 111);
+        assert_eq!(
+            fs.list(fs.lookup("/a").unwrap()),
+            [Entry::Dir("b"), Entry::File("z", 100)]
+        );
+
+        assert_eq!(
+            parse("$ cd /\n$ cd a").unwrap_err().to_string(),
+            "line 2: no such directory \"a\""
+        );
+        assert_eq!(
+            parse("$ ls\n$ rm -rf /").unwrap_err(),
+            ParseError::UnknownCommand {
+                line: 2,
+                command: "rm -rf /".into()
+            }
+        );
+        assert_eq!(
+            parse("$ cd /\n1 a").unwrap_err(),
+            ParseError::UnexpectedOutput { line: 2 }
+        );
+        assert_eq!(
+            parse("$ ls\nabc").unwrap_err(),
+            ParseError::InvalidOutput { line: 2 }
+        );
+        assert_eq!(
+            parse("$").unwrap_err(),
+            ParseError::UnknownCommand {
+                line: 1,
+                command: "".into()
+            }
+        );
+    }
+
+    #[test]
+    fn day7_reports() {
+        let fs = parse(TEST_INPUT).unwrap();
+
+        assert_eq!(
+            fs.tree(),
+            "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)"
+        );
+
+        assert_eq!(fs.du(), "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e");
+
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(u32::MAX), "4.0G");
+    }
+
+    #[test]
+    fn day7_planner() {
+        let fs = parse(TEST_INPUT).unwrap();
+
+        let smallest = plan(&fs, DISK_SIZE, REQUIRED_SPACE, Strategy::SmallestDirectory).unwrap();
+        assert_eq!(smallest.dirs, [fs.lookup("/d").unwrap()]);
+        assert_eq!(smallest.freed, 24933642);
+
+        // Enough space is already available, nothing needs to go
+        assert_eq!(solve_part2("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n10 x"), 0);
+
+        let explanation = smallest.explain(&fs);
+        assert!(explanation.contains("8381165 bytes to free"));
+        assert!(explanation.contains("Delete /d (24933642 bytes)"));
+
+        const PLAN_INPUT: &str = "$ ls
+dir big
+dir m
+dir n
+$ cd big
+$ ls
+100 f
+$ cd /m
+$ ls
+dir s1
+dir s2
+$ cd s1
+$ ls
+30 f
+$ cd ../s2
+$ ls
+30 f
+$ cd /n
+$ ls
+45 f";
+
+        let fs = parse(PLAN_INPUT).unwrap();
+        let paths = |plan: Plan| -> Vec<String> {
+            let mut paths: Vec<_> = plan.dirs.iter().map(|&id| fs.path(id)).collect();
+            paths.sort_unstable();
+            paths
+        };
+
+        let smallest = plan(&fs, 205, 70, Strategy::SmallestDirectory).unwrap();
+        assert_eq!(paths(smallest), ["/big"]);
+
+        let fewest = plan(&fs, 205, 70, Strategy::FewestDirectories).unwrap();
+        assert_eq!(paths(fewest), ["/big"]);
+
+        let minimal = plan(&fs, 205, 70, Strategy::MinimalBytes).unwrap();
+        assert_eq!(minimal.freed, 75);
+        assert_eq!(minimal.dirs.len(), 2);
+
+        assert!(plan(&fs, 205, 120, Strategy::SmallestDirectory).is_none());
+
+        let fewest = plan(&fs, 205, 120, Strategy::FewestDirectories).unwrap();
+        assert_eq!(fewest.freed, 130);
+        assert_eq!(fewest.dirs.len(), 2);
+
+        let minimal = plan(&fs, 205, 150, Strategy::MinimalBytes).unwrap();
+        assert_eq!(paths(minimal), ["/big", "/m"]);
+
+        let nothing = plan(&fs, 300, 50, Strategy::MinimalBytes).unwrap();
+        assert!(nothing.dirs.is_empty());
+
+        assert!(plan(&fs, 205, 206, Strategy::FewestDirectories).is_none());
+    }
+
+    #[test]
+    fn day7_query() {
+        let fs = parse(TEST_INPUT).unwrap();
+        let paths = |query: &str| -> Vec<String> {
+            fs.query(query)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.path)
+                .collect()
+        };
+
+        assert_eq!(paths("dirs where size <= 100000"), ["/a", "/a/e"]);
+        assert_eq!(paths("dirs under /a"), ["/a", "/a/e"]);
+        assert_eq!(
+            paths("files under /a matching *.lst order by size desc"),
+            ["/a/h.lst"]
+        );
+        assert_eq!(
+            paths("files matching d.* order by name"),
+            ["/d/d.ext", "/d/d.log"]
+        );
+        assert_eq!(
+            paths("files where size > 1000 and size < 100000 order by size desc limit 2"),
+            ["/a/h.lst", "/a/f"]
+        );
+        assert_eq!(paths("files under /a/e matching ?"), ["/a/e/i"]);
+        assert_eq!(paths("dirs order by path desc limit 1"), ["/d"]);
+
+        assert_eq!(fs.query("dirs where size"), Err(QueryError::UnexpectedEnd));
+        assert_eq!(
+            fs.query("folders"),
+            Err(QueryError::UnexpectedToken("folders".into()))
+        );
+        assert_eq!(
+            fs.query("dirs where name = a"),
+            Err(QueryError::UnexpectedToken("name".into()))
+        );
+        assert_eq!(
+            fs.query("dirs where size < big"),
+            Err(QueryError::InvalidNumber("big".into()))
+        );
+        assert_eq!(
+            fs.query("files under /x"),
+            Err(QueryError::NoSuchDirectory("/x".into()))
+        );
+    }
+
+    #[test]
+    fn day7_export() {
+        let fs = parse(TEST_INPUT).unwrap();
+
+        let transcript = fs.transcript();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\n14848514 b.txt"));
+        assert!(transcript.contains("$ cd /a/e\n$ ls\n584 i"));
+        assert_eq!(parse(&transcript).unwrap(), fs);
+        assert_eq!(parse(&transcript).unwrap().tree(), fs.tree());
+
+        let real = parse(INPUT).unwrap();
+        let regenerated = parse(&real.transcript()).unwrap();
+        assert_eq!(regenerated, real);
+        assert_eq!(regenerated.to_json(), real.to_json());
+        assert!(real.transcript().lines().count() < INPUT.lines().count());
+
+        let json = parse("$ ls\ndir x\n12 \"y\"").unwrap().to_json();
+        assert_eq!(
+            json,
+            r#"{
+  "name": "/",
+  "type": "dir",
+  "entries": [
+    {
+      "name": "x",
+      "type": "dir",
+      "entries": []
+    },
+    {
+      "name": "\"y\"",
+      "type": "file",
+      "size": 12
+    }
+  ]
+}"#
+        );
+
+        let other = parse(
+            "$ ls
+dir a
+dir d
+dir z
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd /
+$ ls
+14848514 b.txt
+8504156 c.dat
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214297 k",
+        )
+        .unwrap();
+
+        assert_ne!(other, fs);
+        assert_eq!(
+            fs.diff(&other),
+            ["~ /d/k (size=7214296 -> size=7214297)", "+ /z (dir)"]
+        );
+        assert_eq!(other.diff(&fs).len(), 2);
+    }
 }
@@ -1,4 +1,6 @@
-use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 const INPUT: &str = include_str!("day8_input.txt");
 
@@ -14,119 +16,440 @@ fn parse(input: &str) -> (Vec<Vec<i32>>, usize, usize) {
     (grid, rows, cols)
 }
 
-fn iter_cols<R>(grid: &[Vec<i32>], row: usize, range: R) -> Vec<(usize, usize)>
-where
-    R: IntoIterator<Item = usize>,
-{
-    let mut height = -1;
-    let mut visible = vec![];
+struct Analysis {
+    visible: Vec<Vec<bool>>,
+    scores: Vec<Vec<u32>>,
+}
 
-    for col in range {
-        if grid[row][col] > height {
-            height = grid[row][col];
-            visible.push((row, col));
+// Walks a line of trees with a stack of decreasing heights: whatever is left on top
+// once the shorter trees are popped is the first tree blocking the view backward.
+fn scan_line(grid: &[Vec<i32>], line: &[(usize, usize)], analysis: &mut Analysis) {
+    let mut stack: Vec<usize> = vec![];
+
+    for (idx, &(row, col)) in line.iter().enumerate() {
+        let height = grid[row][col];
+
+        while let Some(&top) = stack.last() {
+            let (top_row, top_col) = line[top];
+            if grid[top_row][top_col] >= height {
+                break;
+            }
+            stack.pop();
         }
+
+        let distance = match stack.last() {
+            Some(&top) => idx - top,
+            None => {
+                analysis.visible[row][col] = true;
+                idx
+            }
+        };
+        analysis.scores[row][col] *= distance as u32;
+
+        stack.push(idx);
     }
+}
 
-    visible
+fn analyse(grid: &[Vec<i32>], rows: usize, cols: usize) -> Analysis {
+    let mut analysis = Analysis {
+        visible: vec![vec![false; cols]; rows],
+        scores: vec![vec![1; cols]; rows],
+    };
+
+    for row in 0..rows {
+        let mut line: Vec<_> = (0..cols).map(|col| (row, col)).collect();
+        scan_line(grid, &line, &mut analysis);
+        line.reverse();
+        scan_line(grid, &line, &mut analysis);
+    }
+
+    for col in 0..cols {
+        let mut line: Vec<_> = (0..rows).map(|row| (row, col)).collect();
+        scan_line(grid, &line, &mut analysis);
+        line.reverse();
+        scan_line(grid, &line, &mut analysis);
+    }
+
+    analysis
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Observer {
+    row: i64,
+    col: i64,
+    height: i32,
+}
+
+const COMPASS: [(i64, i64); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+const fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Range of steps k for which start + k * step stays within 0..len
+fn steps_inside(start: i64, step: i64, len: usize) -> Option<(i64, i64)> {
+    let last = len as i64 - 1;
+    let div_ceil = |a: i64, b: i64| (a + b - 1).div_euclid(b);
+
+    match step.signum() {
+        0 => (0..=last).contains(&start).then_some((i64::MIN, i64::MAX)),
+        1 => Some((div_ceil(-start, step), (last - start).div_euclid(step))),
+        _ => Some((div_ceil(start - last, -step), start.div_euclid(-step))),
+    }
 }
 
-fn iter_rows<R>(grid: &[Vec<i32>], col: usize, range: R) -> Vec<(usize, usize)>
-where
-    R: IntoIterator<Item = usize>,
-{
-    let mut height = -1;
+// A tree is seen when its top rises above the sight lines to every tree before it
+// on the ray, comparing the slopes (height - eye height) / distance.
+fn line_of_sight(
+    grid: &[Vec<i32>],
+    rows: usize,
+    cols: usize,
+    observer: Observer,
+    direction: (i64, i64),
+) -> Vec<(usize, usize)> {
+    let (dr, dc) = direction;
+    assert!(dr != 0 || dc != 0, "Invalid direction {direction:?}");
+
+    let divisor = gcd(dr, dc);
+    let (dr, dc) = (dr / divisor, dc / divisor);
+
+    let (Some((row_first, row_last)), Some((col_first, col_last))) = (
+        steps_inside(observer.row, dr, rows),
+        steps_inside(observer.col, dc, cols),
+    ) else {
+        return vec![];
+    };
+
     let mut visible = vec![];
+    let mut highest: Option<(i64, i64)> = None;
+
+    for k in row_first.max(col_first).max(1)..=row_last.min(col_last) {
+        let (row, col) = (
+            (observer.row + k * dr) as usize,
+            (observer.col + k * dc) as usize,
+        );
+        let rise = i64::from(grid[row][col] - observer.height);
 
-    for row in range {
-        if grid[row][col] > height {
-            height = grid[row][col];
+        if highest.is_none_or(|(best_rise, best_k)| rise * best_k > best_rise * k) {
             visible.push((row, col));
+            highest = Some((rise, k));
         }
     }
 
     visible
 }
 
-fn solve_part1(input: &str) -> usize {
-    let (grid, rows, cols) = parse(input);
-    let mut visible: HashSet<(usize, usize)> = HashSet::new();
+fn visible_from(
+    grid: &[Vec<i32>],
+    rows: usize,
+    cols: usize,
+    observer: Observer,
+    directions: &[(i64, i64)],
+) -> Vec<(usize, usize)> {
+    let mut visible: Vec<_> = directions
+        .iter()
+        .flat_map(|&direction| line_of_sight(grid, rows, cols, observer, direction))
+        .collect();
 
-    for row in 0..rows {
-        visible.extend(iter_cols(&grid, row, 0..cols).into_iter());
-        visible.extend(iter_cols(&grid, row, (0..cols).rev()).into_iter());
+    visible.sort_unstable();
+    visible.dedup();
+    visible
+}
+
+impl Analysis {
+    fn visible_matrix(&self) -> Vec<Vec<u32>> {
+        self.visible
+            .iter()
+            .map(|row| row.iter().map(|&v| u32::from(v)).collect())
+            .collect()
     }
 
-    for col in 0..cols {
-        visible.extend(iter_rows(&grid, col, 0..rows).into_iter());
-        visible.extend(iter_rows(&grid, col, (0..rows).rev()).into_iter());
+    fn best_tree(&self) -> Option<(usize, usize)> {
+        let max = self.scores.iter().flatten().max()?;
+
+        self.scores.iter().enumerate().find_map(|(row, scores)| {
+            scores
+                .iter()
+                .position(|score| score == max)
+                .map(|col| (row, col))
+        })
     }
+}
 
-    visible.len()
+fn scale(value: u32, max: u32, to: u32) -> u32 {
+    if max == 0 {
+        0
+    } else {
+        (u64::from(value) * u64::from(to) / u64::from(max)) as u32
+    }
 }
 
-fn solve_part2(input: &str) -> u32 {
-    let (grid, rows, cols) = parse(input);
+fn to_csv(matrix: &[Vec<u32>]) -> String {
+    matrix
+        .iter()
+        .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    let mut max_score = 0;
+// Plain PGM, values are rescaled to 0..=255 when normalising or when they don't
+// fit in the format 16 bits
+fn to_pgm(matrix: &[Vec<u32>], normalise: bool) -> String {
+    let rows = matrix.len();
+    let cols = matrix.first().map_or(0, Vec::len);
+    let max = matrix.iter().flatten().copied().max().unwrap_or(0);
 
-    for start_y in 1..rows - 1 {
-        for start_x in 1..cols - 1 {
-            let start_height = grid[start_y][start_x];
+    let (maxval, rescale) = match (normalise, max) {
+        (true, _) => (255, true),
+        (false, 0) => (1, false),
+        (false, 1..=65535) => (max, false),
+        (false, _) => (65535, true),
+    };
 
-            let score = [(0, -1), (-1, 0), (0, 1), (1, 0)]
-                .into_iter()
-                .map(|(inc_x, inc_y)| {
-                    let mut count = 0;
-                    let (mut x, mut y) = (start_x as i32 + inc_x, start_y as i32 + inc_y);
+    let mut lines = vec![
+        "P2".to_string(),
+        format!("{cols} {rows}"),
+        maxval.to_string(),
+    ];
 
-                    loop {
-                        if !(0..cols as i32).contains(&x) || !(0..rows as i32).contains(&y) {
-                            break;
-                        }
+    for row in matrix {
+        let values: Vec<_> = row
+            .iter()
+            .map(|&v| if rescale { scale(v, max, maxval) } else { v })
+            .map(|v| v.to_string())
+            .collect();
+        lines.push(values.join(" "));
+    }
 
-                        count += 1;
+    lines.join("\n")
+}
 
-                        if grid[y as usize][x as usize] < start_height {
-                            x += inc_x;
-                            y += inc_y;
-                        } else {
-                            break;
-                        }
-                    }
+// Plain PPM with scenic scores in green and visible trees in blue, the best tree
+// being drawn in red when highlighted
+fn to_ppm(analysis: &Analysis, highlight_best: bool) -> String {
+    let rows = analysis.scores.len();
+    let cols = analysis.scores.first().map_or(0, Vec::len);
+    let max = analysis.scores.iter().flatten().copied().max().unwrap_or(0);
+    let best = analysis.best_tree().filter(|_| highlight_best);
 
-                    count
-                })
-                .product();
+    let mut lines = vec![
+        "P3".to_string(),
+        format!("{cols} {rows}"),
+        "255".to_string(),
+    ];
 
-            max_score = std::cmp::max(max_score, score);
-        }
+    for (row, scores) in analysis.scores.iter().enumerate() {
+        let pixels: Vec<_> = scores
+            .iter()
+            .enumerate()
+            .map(|(col, &score)| {
+                if best == Some((row, col)) {
+                    "255 0 0".to_string()
+                } else {
+                    let blue = if analysis.visible[row][col] { 255 } else { 0 };
+                    format!("0 {} {blue}", scale(score, max, 255))
+                }
+            })
+            .collect();
+        lines.push(pixels.join("  "));
     }
 
-    max_score
+    lines.join("\n")
+}
+
+fn export(input: &str, dir: &Path, normalise: bool, highlight_best: bool) -> io::Result<()> {
+    let (grid, rows, cols) = parse(input);
+    let analysis = analyse(&grid, rows, cols);
+    let visible = analysis.visible_matrix();
+
+    fs::write(dir.join("visible.csv"), to_csv(&visible))?;
+    fs::write(dir.join("scores.csv"), to_csv(&analysis.scores))?;
+    fs::write(dir.join("visible.pgm"), to_pgm(&visible, normalise))?;
+    fs::write(dir.join("scores.pgm"), to_pgm(&analysis.scores, normalise))?;
+    fs::write(dir.join("forest.ppm"), to_ppm(&analysis, highlight_best))?;
+
+    Ok(())
+}
+
+fn solve_part1(input: &str) -> usize {
+    let (grid, rows, cols) = parse(input);
+
+    analyse(&grid, rows, cols)
+        .visible
+        .into_iter()
+        .flatten()
+        .filter(|&v| v)
+        .count()
+}
+
+fn solve_part2(input: &str) -> u32 {
+    let (grid, rows, cols) = parse(input);
+
+    analyse(&grid, rows, cols)
+        .scores
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap()
 }
 
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        ["--observer", row, col, height] => {
+            let (grid, rows, cols) = parse(INPUT);
+            let observer = Observer {
+                row: row.parse().unwrap(),
+                col: col.parse().unwrap(),
+                height: height.parse().unwrap(),
+            };
+
+            let visible = visible_from(&grid, rows, cols, observer, &COMPASS);
+            println!("Visible trees from {observer:?}: {}", visible.len());
+        }
+        ["--export", dir, ref options @ ..] => {
+            let normalise = options.contains(&"--normalise");
+            let highlight_best = options.contains(&"--highlight-best");
+
+            export(INPUT, Path::new(dir), normalise, highlight_best).unwrap();
+        }
+        _ => {}
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn day7() {
-        const TEST_INPUT: &str = "30373
+    const TEST_INPUT: &str = "30373
 25512
 65332
 33549
 35390";
 
+    #[test]
+    fn day7() {
         assert_eq!(solve_part1(TEST_INPUT), 21);
         assert_eq!(solve_part1(INPUT), 1662);
 
         assert_eq!(solve_part2(TEST_INPUT), 8);
         assert_eq!(solve_part2(INPUT), 537600);
     }
+
+    #[test]
+    fn day8_analysis() {
+        let (grid, rows, cols) = parse(TEST_INPUT);
+        let analysis = analyse(&grid, rows, cols);
+
+        let visible: Vec<String> = analysis
+            .visible
+            .iter()
+            .map(|row| row.iter().map(|&v| if v { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(visible, ["#####", "###.#", "##.##", "#.#.#", "#####"]);
+
+        assert_eq!(analysis.scores[1][2], 4);
+        assert_eq!(analysis.scores[3][2], 8);
+        assert_eq!(analysis.scores[0], [0; 5]);
+        assert_eq!(analysis.scores[2], [0, 6, 1, 2, 0]);
+    }
+
+    #[test]
+    fn day8_line_of_sight() {
+        let (grid, rows, cols) = parse(TEST_INPUT);
+        let at = |row, col, height| Observer { row, col, height };
+
+        let canopy = visible_from(&grid, rows, cols, at(2, 2, 100), &COMPASS);
+        assert_eq!(canopy.len(), 16);
+
+        assert_eq!(
+            line_of_sight(&grid, rows, cols, at(-1, 2, 0), (1, 0)),
+            [(0, 2)]
+        );
+        assert_eq!(
+            line_of_sight(&grid, rows, cols, at(-3, 2, 0), (1, 0)),
+            [(0, 2), (1, 2)]
+        );
+        assert_eq!(
+            line_of_sight(&grid, rows, cols, at(2, 2, 3), (0, 1)),
+            [(2, 3)]
+        );
+
+        let slope = line_of_sight(&grid, rows, cols, at(0, 0, 9), (1, 2));
+        assert_eq!(slope, [(1, 2), (2, 4)]);
+        assert_eq!(line_of_sight(&grid, rows, cols, at(0, 0, 9), (2, 4)), slope);
+        assert_eq!(
+            line_of_sight(&grid, rows, cols, at(-2, -4, 9), (1, 2)),
+            [(0, 0), (1, 2)]
+        );
+
+        assert!(line_of_sight(&grid, rows, cols, at(-5, -5, 0), (0, 1)).is_empty());
+        assert!(line_of_sight(&grid, rows, cols, at(10, 2, 0), (1, 0)).is_empty());
+        assert_eq!(
+            line_of_sight(&grid, rows, cols, at(4, 4, 0), (-1, -1)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn day8_heatmap() {
+        let (grid, rows, cols) = parse(TEST_INPUT);
+        let analysis = analyse(&grid, rows, cols);
+
+        assert_eq!(analysis.best_tree(), Some((3, 2)));
+
+        let csv = to_csv(&analysis.scores);
+        assert_eq!(csv.lines().nth(2), Some("0,6,1,2,0"));
+        assert_eq!(
+            to_csv(&analysis.visible_matrix()).lines().nth(1),
+            Some("1,1,1,0,1")
+        );
+
+        let pgm = to_pgm(&analysis.scores, false);
+        assert!(pgm.starts_with("P2\n5 5\n8\n0 0 0 0 0\n0 1 4 1 0\n"));
+
+        let pgm = to_pgm(&analysis.scores, true);
+        assert_eq!(pgm.lines().nth(2), Some("255"));
+        assert_eq!(pgm.lines().nth(6), Some("0 31 255 95 0"));
+
+        assert_eq!(to_pgm(&[vec![0, 70000]], false), "P2\n2 1\n65535\n0 65535");
+
+        let ppm = to_ppm(&analysis, true);
+        let best_row = ppm.lines().nth(6).unwrap();
+        assert_eq!(best_row.split("  ").nth(2), Some("255 0 0"));
+        assert_eq!(best_row.split("  ").nth(1), Some("0 31 0"));
+
+        let ppm = to_ppm(&analysis, false);
+        assert_eq!(
+            ppm.lines().nth(6).unwrap().split("  ").nth(2),
+            Some("0 255 255")
+        );
+
+        // Unique per run so concurrent test runs don't clash
+        let dir = std::env::temp_dir().join(format!("aoc2022_day8_heatmap_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        export(INPUT, &dir, true, true).unwrap();
+        assert!(fs::read_to_string(dir.join("forest.ppm"))
+            .unwrap()
+            .starts_with("P3\n99 99\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,71 +1,629 @@
-use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::{Add, Sub};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 const INPUT: &str = include_str!("day9_input.txt");
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 struct Position {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
 impl Position {
-    fn distance(self, o: Self) -> f64 {
-        f64::from((self.x - o.x).pow(2) + (self.y - o.y).pow(2)).sqrt()
+    fn chebyshev(self, o: Self) -> i64 {
+        (self.x - o.x).abs().max((self.y - o.y).abs())
+    }
+
+    // Moves one step towards the leader if no longer touching it, returns
+    // whether the knot moved
+    fn follow(&mut self, leader: Self) -> bool {
+        if self.chebyshev(leader) < 2 {
+            return false;
+        }
+
+        self.x += (leader.x - self.x).signum();
+        self.y += (leader.y - self.y).signum();
+        true
+    }
+
+    fn scaled(self, n: i64) -> Self {
+        Position {
+            x: self.x * n,
+            y: self.y * n,
+        }
     }
 }
 
-fn solve(input: &str, count: usize) -> usize {
-    let mut knots = vec![Position::default(); count];
-    let mut tail_positions: HashSet<Position> = HashSet::new();
+impl Add for Position {
+    type Output = Self;
 
-    for l in input.lines() {
-        let (dir, count) = l.split_once(' ').unwrap();
+    fn add(self, o: Self) -> Self {
+        Position {
+            x: self.x + o.x,
+            y: self.y + o.y,
+        }
+    }
+}
 
-        for _ in 0..count.parse::<usize>().unwrap() {
-            let head = &mut knots[0];
-            match dir {
-                "R" => {
-                    head.x += 1;
-                }
-                "L" => {
-                    head.x -= 1;
-                }
-                "U" => {
-                    head.y += 1;
-                }
-                "D" => {
-                    head.y -= 1;
+impl Sub for Position {
+    type Output = Self;
+
+    fn sub(self, o: Self) -> Self {
+        Position {
+            x: self.x - o.x,
+            y: self.y - o.y,
+        }
+    }
+}
+
+// Straight line along one of the four unit step directions, identified by the
+// step and the value `a * x + b * y` shared by all of its cells
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Line {
+    step: Position,
+    key: i64,
+}
+
+impl Line {
+    const STEPS: [Position; 4] = [
+        Position { x: 1, y: 0 },
+        Position { x: 0, y: 1 },
+        Position { x: 1, y: 1 },
+        Position { x: 1, y: -1 },
+    ];
+
+    fn coefficients(step: Position) -> (i64, i64) {
+        match (step.x, step.y) {
+            (1, 0) => (0, 1),
+            (0, 1) => (1, 0),
+            (1, 1) => (-1, 1),
+            (1, -1) => (1, 1),
+            _ => unreachable!(),
+        }
+    }
+
+    // Line through the position along the step, with the position's offset on it
+    fn through(pos: Position, step: Position) -> (Self, i64) {
+        let step = if step.x < 0 || (step.x == 0 && step.y < 0) {
+            step.scaled(-1)
+        } else {
+            step
+        };
+        let (a, b) = Line::coefficients(step);
+        let offset = if step.x == 0 { pos.y } else { pos.x };
+
+        (
+            Line {
+                step,
+                key: a * pos.x + b * pos.y,
+            },
+            offset,
+        )
+    }
+
+    fn intersection(self, o: Self) -> Option<Position> {
+        let (a1, b1) = Line::coefficients(self.step);
+        let (a2, b2) = Line::coefficients(o.step);
+        // Wide enough for the products of keys near the ends of the range
+        let (k1, k2) = (i128::from(self.key), i128::from(o.key));
+        let det = i128::from(a1 * b2 - a2 * b1);
+
+        if det == 0 {
+            return None;
+        }
+
+        let x = k1 * i128::from(b2) - k2 * i128::from(b1);
+        let y = i128::from(a1) * k2 - i128::from(a2) * k1;
+
+        // Diagonals of opposite parity never meet on a cell
+        if x % det != 0 || y % det != 0 {
+            return None;
+        }
+
+        Some(Position {
+            x: (x / det) as i64,
+            y: (y / det) as i64,
+        })
+    }
+}
+
+// Cells visited by a knot, as single cells from step by step moves and as
+// inclusive offset ranges along lines from bulk moves
+#[derive(Clone, Debug, Default)]
+struct Trail {
+    cells: HashSet<Position>,
+    lines: HashMap<Line, Vec<(i64, i64)>>,
+}
+
+impl Trail {
+    fn insert(&mut self, pos: Position) {
+        self.cells.insert(pos);
+    }
+
+    // Records `len` cells starting at `start` and moving by `step`
+    fn insert_segment(&mut self, start: Position, step: Position, len: i64) {
+        if len <= 0 {
+            return;
+        }
+
+        let (line, from) = Line::through(start, step);
+        let (_, to) = Line::through(start + step.scaled(len - 1), step);
+
+        self.lines
+            .entry(line)
+            .or_default()
+            .push((from.min(to), from.max(to)));
+    }
+
+    fn merged(&self) -> HashMap<Line, Vec<(i64, i64)>> {
+        self.lines
+            .iter()
+            .map(|(&line, ranges)| {
+                let mut ranges = ranges.clone();
+                ranges.sort_unstable();
+
+                let mut merged: Vec<(i64, i64)> = vec![];
+                for (from, to) in ranges {
+                    match merged.last_mut() {
+                        Some(last) if from <= last.1 + 1 => last.1 = last.1.max(to),
+                        _ => merged.push((from, to)),
+                    }
                 }
-                _ => {
-                    panic!();
+
+                (line, merged)
+            })
+            .collect()
+    }
+
+    fn covering(merged: &HashMap<Line, Vec<(i64, i64)>>, pos: Position) -> usize {
+        Line::STEPS
+            .iter()
+            .filter(|&&step| {
+                let (line, offset) = Line::through(pos, step);
+                merged.get(&line).is_some_and(|ranges| {
+                    let idx = ranges.partition_point(|&(_, to)| to < offset);
+                    ranges.get(idx).is_some_and(|&(from, _)| from <= offset)
+                })
+            })
+            .count()
+    }
+
+    fn contains(&self, pos: &Position) -> bool {
+        self.cells.contains(pos)
+            || Line::STEPS.iter().any(|&step| {
+                let (line, offset) = Line::through(*pos, step);
+                self.lines.get(&line).is_some_and(|ranges| {
+                    ranges
+                        .iter()
+                        .any(|&(from, to)| (from..=to).contains(&offset))
+                })
+            })
+    }
+
+    fn len(&self) -> usize {
+        let merged = self.merged();
+
+        let mut total: usize = merged
+            .values()
+            .flatten()
+            .map(|&(from, to)| (to - from) as usize + 1)
+            .sum();
+
+        // Cells where lines cross were counted once per line
+        let lines: Vec<_> = merged.keys().copied().collect();
+        let mut crossings = HashSet::new();
+        for (i, a) in lines.iter().enumerate() {
+            for b in &lines[i + 1..] {
+                if let Some(pos) = a.intersection(*b) {
+                    crossings.insert(pos);
                 }
             }
+        }
+
+        for pos in crossings {
+            total -= Trail::covering(&merged, pos).saturating_sub(1);
+        }
+
+        total
+            + self
+                .cells
+                .iter()
+                .filter(|&&pos| Trail::covering(&merged, pos) == 0)
+                .count()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    Stepwise,
+    Accelerated,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Direction {
+    Right,
+    Left,
+    Up,
+    Down,
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+}
+
+impl Direction {
+    fn delta(self) -> Position {
+        match self {
+            Direction::Right => Position { x: 1, y: 0 },
+            Direction::Left => Position { x: -1, y: 0 },
+            Direction::Up => Position { x: 0, y: 1 },
+            Direction::Down => Position { x: 0, y: -1 },
+            Direction::UpRight => Position { x: 1, y: 1 },
+            Direction::UpLeft => Position { x: -1, y: 1 },
+            Direction::DownRight => Position { x: 1, y: -1 },
+            Direction::DownLeft => Position { x: -1, y: -1 },
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "R" => Ok(Direction::Right),
+            "L" => Ok(Direction::Left),
+            "U" => Ok(Direction::Up),
+            "D" => Ok(Direction::Down),
+            "UR" => Ok(Direction::UpRight),
+            "UL" => Ok(Direction::UpLeft),
+            "DR" => Ok(Direction::DownRight),
+            "DL" => Ok(Direction::DownLeft),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Direction::Right => "R",
+            Direction::Left => "L",
+            Direction::Up => "U",
+            Direction::Down => "D",
+            Direction::UpRight => "UR",
+            Direction::UpLeft => "UL",
+            Direction::DownRight => "DR",
+            Direction::DownLeft => "DL",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Move {
+    // `R 4`, `UR 2`
+    Step { direction: Direction, count: usize },
+    // `by 3 -2`, relative to the head
+    By(Position),
+    // `to 10 5`, absolute coordinates for the head
+    To(Position),
+}
+
+impl FromStr for Move {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split_whitespace().collect();
+        let coordinates = |x: &str, y: &str| -> Result<Position, ()> {
+            Ok(Position {
+                x: x.parse().map_err(|_| ())?,
+                y: y.parse().map_err(|_| ())?,
+            })
+        };
+
+        match parts[..] {
+            ["by", x, y] => Ok(Move::By(coordinates(x, y)?)),
+            ["to", x, y] => Ok(Move::To(coordinates(x, y)?)),
+            // A single move can't take the head further than the coordinates reach
+            [direction, count] => Ok(Move::Step {
+                direction: direction.parse()?,
+                count: count
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|count| usize::try_from(count).ok())
+                    .ok_or(())?,
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Step { direction, count } => write!(f, "{direction} {count}"),
+            Move::By(v) => write!(f, "by {} {}", v.x, v.y),
+            Move::To(p) => write!(f, "to {} {}", p.x, p.y),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Rope {
+    knots: Vec<Position>,
+    visited: Vec<Trail>,
+}
+
+impl Rope {
+    fn new(count: usize) -> Self {
+        assert!(count > 0, "a rope needs at least one knot");
+
+        Rope {
+            knots: vec![Position::default(); count],
+            visited: (0..count)
+                .map(|_| {
+                    let mut trail = Trail::default();
+                    trail.insert(Position::default());
+                    trail
+                })
+                .collect(),
+        }
+    }
+
+    // Moves the head by one step and lets the rest of the rope catch up,
+    // returns whether every knot moved by the same delta as the head
+    fn step(&mut self, delta: Position) -> bool {
+        self.knots[0] = self.knots[0] + delta;
+        self.visited[0].insert(self.knots[0]);
+
+        let mut rigid = true;
+        for idx in 1..self.knots.len() {
+            let leader = self.knots[idx - 1];
+            let before = self.knots[idx];
+
+            // Knots further down can't move if this one didn't
+            if !self.knots[idx].follow(leader) {
+                return false;
+            }
+
+            self.visited[idx].insert(self.knots[idx]);
+            rigid &= self.knots[idx] - before == delta;
+        }
+
+        rigid
+    }
+
+    // Every knot sits right behind its leader, so each further step moves the
+    // whole rope by the same delta
+    fn is_rigid(&self, delta: Position) -> bool {
+        self.knots.windows(2).all(|w| w[0] - w[1] == delta)
+    }
+
+    fn advance(&mut self, delta: Position, count: usize) {
+        let n = i64::try_from(count).expect("move count exceeds the coordinate range");
+
+        for (knot, trail) in self.knots.iter_mut().zip(&mut self.visited) {
+            trail.insert_segment(*knot + delta, delta, n);
+            *knot = *knot + delta.scaled(n);
+        }
+    }
+
+    // Splits a move into straight runs of single steps for the head, going
+    // diagonally first like the knots do when they catch up
+    fn runs(&self, m: Move) -> Vec<(Position, usize)> {
+        let offset = match m {
+            Move::Step { direction, count } => return vec![(direction.delta(), count)],
+            Move::By(v) => v,
+            Move::To(p) => p - self.knots[0],
+        };
+
+        let (dx, dy) = (offset.x.unsigned_abs(), offset.y.unsigned_abs());
+        let diagonal = Position {
+            x: offset.x.signum(),
+            y: offset.y.signum(),
+        };
+        let straight = if dx > dy {
+            Position {
+                x: diagonal.x,
+                y: 0,
+            }
+        } else {
+            Position {
+                x: 0,
+                y: diagonal.y,
+            }
+        };
 
-            for idx in 1..knots.len() {
-                let prev = knots[idx - 1];
-                let cur = &mut knots[idx];
-
-                if cur.distance(prev) >= 2.0 {
-                    cur.x += match prev.x.cmp(&cur.x) {
-                        Ordering::Greater => 1,
-                        Ordering::Less => -1,
-                        Ordering::Equal => 0,
-                    };
-
-                    cur.y += match prev.y.cmp(&cur.y) {
-                        Ordering::Greater => 1,
-                        Ordering::Less => -1,
-                        Ordering::Equal => 0,
-                    };
+        vec![
+            (diagonal, dx.min(dy) as usize),
+            (straight, dx.abs_diff(dy) as usize),
+        ]
+        .into_iter()
+        .filter(|&(_, count)| count > 0)
+        .collect()
+    }
+
+    fn apply(&mut self, m: Move, mode: Mode) {
+        for (delta, count) in self.runs(m) {
+            // Once the whole rope moved by the delta the offsets between knots
+            // repeat, so every later step of the run moves it the same way
+            let mut moved_rigidly = false;
+
+            for done in 0..count {
+                if mode == Mode::Accelerated && (moved_rigidly || self.is_rigid(delta)) {
+                    self.advance(delta, count - done);
+                    break;
                 }
+
+                moved_rigidly = self.step(delta);
             }
+        }
+    }
+
+    fn visited(&self, knot: usize) -> usize {
+        self.visited[knot].len()
+    }
 
-            tail_positions.insert(*knots.last().unwrap());
+    fn tail_visited(&self) -> usize {
+        self.visited(self.knots.len() - 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Viewport {
+    left: i64,
+    bottom: i64,
+    width: i64,
+    height: i64,
+}
+
+impl Viewport {
+    fn centered(center: Position, width: i64, height: i64) -> Self {
+        Viewport {
+            left: center.x - width / 2,
+            bottom: center.y - height / 2,
+            width,
+            height,
         }
     }
 
-    tail_positions.len()
+    // Scrolls as little as possible to keep the whole rope in view, or follows
+    // the head if the rope doesn't fit
+    fn track(&mut self, knots: &[Position]) {
+        let min_x = knots.iter().map(|k| k.x).min().unwrap();
+        let max_x = knots.iter().map(|k| k.x).max().unwrap();
+        let min_y = knots.iter().map(|k| k.y).min().unwrap();
+        let max_y = knots.iter().map(|k| k.y).max().unwrap();
+
+        if max_x - min_x >= self.width || max_y - min_y >= self.height {
+            *self = Viewport::centered(knots[0], self.width, self.height);
+            return;
+        }
+
+        if min_x < self.left {
+            self.left = min_x;
+        } else if max_x >= self.left + self.width {
+            self.left = max_x - self.width + 1;
+        }
+
+        if min_y < self.bottom {
+            self.bottom = min_y;
+        } else if max_y >= self.bottom + self.height {
+            self.bottom = max_y - self.height + 1;
+        }
+    }
+}
+
+fn knot_label(idx: usize, count: usize) -> char {
+    match idx {
+        0 => 'H',
+        _ if count == 2 => 'T',
+        1..=9 => char::from_digit(idx as u32, 10).unwrap(),
+        _ => '+',
+    }
+}
+
+// Draws the rope like the puzzle does, with the first knot covering the ones
+// behind it, then the start and the cells visited by the tail
+fn render(rope: &Rope, viewport: &Viewport) -> String {
+    let trail = rope.visited.last().unwrap();
+    let mut lines = vec![];
+
+    for y in (viewport.bottom..viewport.bottom + viewport.height).rev() {
+        let line: String = (viewport.left..viewport.left + viewport.width)
+            .map(|x| {
+                let pos = Position { x, y };
+
+                if let Some(idx) = rope.knots.iter().position(|&k| k == pos) {
+                    knot_label(idx, rope.knots.len())
+                } else if pos == Position::default() {
+                    's'
+                } else if trail.contains(&pos) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+fn animate(input: &str, count: usize, mut viewport: Viewport) -> Vec<String> {
+    let mut rope = Rope::new(count);
+    let mut frames = vec![format!(
+        "== Initial State ==\n\n{}",
+        render(&rope, &viewport)
+    )];
+
+    for m in parse(input) {
+        let steps: Vec<_> = rope
+            .runs(m)
+            .into_iter()
+            .flat_map(|(delta, count)| (0..count).map(move |_| delta))
+            .collect();
+
+        for (idx, &delta) in steps.iter().enumerate() {
+            rope.step(delta);
+            viewport.track(&rope.knots);
+
+            frames.push(format!(
+                "== {m} ({}/{}) ==\n\n{}",
+                idx + 1,
+                steps.len(),
+                render(&rope, &viewport)
+            ));
+        }
+    }
+
+    frames
+}
+
+fn write_frames(path: &Path, frames: &[String]) -> io::Result<()> {
+    fs::write(path, frames.join("\n\n"))
+}
+
+fn play(frames: &[String], delay: Duration) {
+    for frame in frames {
+        // Clear the terminal and move the cursor back to the top left
+        println!("\x1b[2J\x1b[H{frame}");
+        thread::sleep(delay);
+    }
+}
+
+fn parse(input: &str) -> Vec<Move> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| {
+            l.parse()
+                .unwrap_or_else(|_| panic!("Invalid move on line {}: {l}", idx + 1))
+        })
+        .collect()
+}
+
+fn simulate(input: &str, count: usize, mode: Mode) -> Rope {
+    let mut rope = Rope::new(count);
+
+    for m in parse(input) {
+        rope.apply(m, mode);
+    }
+
+    rope
+}
+
+fn solve(input: &str, count: usize) -> usize {
+    simulate(input, count, Mode::Accelerated).tail_visited()
 }
 
 fn solve_part1(input: &str) -> usize {
@@ -79,6 +637,34 @@ fn solve_part2(input: &str) -> usize {
 fn main() {
     println!("Part 1: {}", solve_part1(INPUT));
     println!("Part 2: {}", solve_part2(INPUT));
+
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    let viewport = Viewport::centered(Position::default(), 80, 24);
+
+    match args[..] {
+        ["--knots", count, ref options @ ..] => {
+            let mode = if options.contains(&"--stepwise") {
+                Mode::Stepwise
+            } else {
+                Mode::Accelerated
+            };
+
+            let rope = simulate(INPUT, count.parse().unwrap(), mode);
+            for knot in 0..rope.knots.len() {
+                println!("Knot {knot} visited {} cells", rope.visited(knot));
+            }
+        }
+        ["--animate", path, count] => {
+            let frames = animate(INPUT, count.parse().unwrap(), viewport);
+            write_frames(Path::new(path), &frames).unwrap();
+        }
+        ["--play", count, delay] => {
+            let frames = animate(INPUT, count.parse().unwrap(), viewport);
+            play(&frames, Duration::from_millis(delay.parse().unwrap()));
+        }
+        _ => {}
+    }
 }
 
 #[cfg(test)]
@@ -111,4 +697,278 @@ U 20";
         assert_eq!(solve_part2(TEST_INPUT2), 36);
         assert_eq!(solve_part2(INPUT), 2458);
     }
+
+    #[test]
+    fn day9_rope() {
+        let mut rope = Rope::new(3);
+        rope.apply("R 2".parse().unwrap(), Mode::Stepwise);
+        assert_eq!(
+            rope.knots,
+            [
+                Position { x: 2, y: 0 },
+                Position { x: 1, y: 0 },
+                Position::default()
+            ]
+        );
+
+        rope.step(Position { x: 0, y: 1 });
+        rope.step(Position { x: 0, y: 1 });
+        assert_eq!(
+            rope.knots,
+            [
+                Position { x: 2, y: 2 },
+                Position { x: 2, y: 1 },
+                Position { x: 1, y: 1 }
+            ]
+        );
+        assert_eq!(rope.visited(0), 5);
+        assert_eq!(rope.visited(1), 3);
+        assert_eq!(rope.tail_visited(), 2);
+
+        // Each knot moves the same way whatever the length of the rope behind it
+        let rope = simulate(INPUT, 10, Mode::Stepwise);
+        assert_eq!(rope.visited(1), 6271);
+        assert_eq!(rope.visited(9), 2458);
+
+        assert_eq!(
+            solve(TEST_INPUT1, 1),
+            simulate(TEST_INPUT1, 1, Mode::Stepwise).visited(0)
+        );
+        assert!(solve(TEST_INPUT2, 12) < solve(TEST_INPUT2, 10));
+
+        assert_eq!("X 3".parse::<Move>(), Err(()));
+        assert_eq!("R x".parse::<Move>(), Err(()));
+        assert_eq!("R -1".parse::<Move>(), Err(()));
+        assert_eq!("R 9223372036854775808".parse::<Move>(), Err(()));
+        assert_eq!(
+            "R 3000000000".parse::<Move>(),
+            Ok(Move::Step {
+                direction: Direction::Right,
+                count: 3_000_000_000
+            })
+        );
+        assert_eq!(
+            "R 2147483647".parse::<Move>(),
+            Ok(Move::Step {
+                direction: Direction::Right,
+                count: i32::MAX as usize
+            })
+        );
+    }
+
+    #[test]
+    fn day9_animation() {
+        let viewport = Viewport {
+            left: 0,
+            bottom: 0,
+            width: 6,
+            height: 5,
+        };
+
+        let frames = animate(TEST_INPUT1, 2, viewport);
+        assert_eq!(frames.len(), 25);
+        assert_eq!(
+            frames[0],
+            "== Initial State ==\n\n......\n......\n......\n......\nH....."
+        );
+        assert_eq!(
+            frames[2],
+            "== R 4 (2/4) ==\n\n......\n......\n......\n......\nsTH..."
+        );
+        assert_eq!(
+            frames[24],
+            "== R 2 (2/2) ==\n\n..##..\n...##.\n.TH##.\n....#.\ns###.."
+        );
+
+        let mut rope = Rope::new(10);
+        rope.apply("R 4".parse().unwrap(), Mode::Stepwise);
+        assert_eq!(render(&rope, &viewport).lines().last(), Some("4321H."));
+
+        let mut viewport = Viewport::centered(Position::default(), 5, 5);
+        viewport.track(&[Position { x: 3, y: 0 }, Position { x: 2, y: -4 }]);
+        assert_eq!(viewport.left, -1);
+        assert_eq!(viewport.bottom, -4);
+
+        viewport.track(&[Position { x: 10, y: 0 }, Position { x: 0, y: 0 }]);
+        assert_eq!(viewport, Viewport::centered(Position { x: 10, y: 0 }, 5, 5));
+
+        // Unique per run so concurrent test runs don't clash
+        let path =
+            std::env::temp_dir().join(format!("aoc2022_day9_frames_{}.txt", std::process::id()));
+        write_frames(&path, &frames[..2]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), frames[..2].join("\n\n"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn day9_accelerated() {
+        let spiral = "R 50\nU 30\nL 80\nD 60\nR 40\nU 100\nL 3\nD 7\nR 1\nU 1\nL 45\nD 90";
+
+        // Pseudo random moves with long runs, crossing and overlapping trails
+        let mut seed = 7u32;
+        let random: Vec<_> = (0..200)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let direction = ["R", "L", "U", "D"][(seed >> 16) as usize % 4];
+                format!("{direction} {}", (seed >> 20) % 40)
+            })
+            .collect();
+        let random = random.join("\n");
+
+        for input in [TEST_INPUT1, TEST_INPUT2, INPUT, spiral, &random] {
+            for count in [1, 2, 3, 10] {
+                let stepwise = simulate(input, count, Mode::Stepwise);
+                let accelerated = simulate(input, count, Mode::Accelerated);
+
+                assert_eq!(stepwise.knots, accelerated.knots);
+                for knot in 0..count {
+                    assert_eq!(stepwise.visited(knot), accelerated.visited(knot));
+                }
+            }
+        }
+
+        // The trails grow linearly with the move length past the turns
+        let there_and_back = |n: usize| format!("R {n}\nU 5\nL {n}");
+        let tail =
+            |n: usize| simulate(&there_and_back(n), 10, Mode::Stepwise).tail_visited() as i64;
+        let slope = (tail(2000) - tail(1000)) / 1000;
+        assert_eq!(tail(3000) - tail(2000), slope * 1000);
+        let offset = tail(1000) - slope * 1000;
+
+        // Past the range of 32 bit coordinates
+        let rope = simulate("R 2147483647\nR 2147483647\nR 1", 3, Mode::Accelerated);
+        assert_eq!(
+            rope.knots[0],
+            Position {
+                x: 4294967295,
+                y: 0
+            }
+        );
+        assert_eq!(rope.tail_visited(), 4294967294);
+
+        let rope = simulate(
+            "L 5\nto 2147483647 0\nto 4294967294 3",
+            2,
+            Mode::Accelerated,
+        );
+        assert_eq!(
+            rope.knots[0],
+            Position {
+                x: 4294967294,
+                y: 3
+            }
+        );
+        assert_eq!(
+            rope.knots[1],
+            Position {
+                x: 4294967293,
+                y: 3
+            }
+        );
+        assert_eq!(rope.visited(0), 6 + 2 * 2147483647);
+
+        let rope = simulate(&there_and_back(1_000_000_000), 10, Mode::Accelerated);
+        assert_eq!(rope.knots[0], Position { x: 0, y: 5 });
+        assert_eq!(rope.visited(0), 2_000_000_006);
+        assert_eq!(rope.tail_visited() as i64, slope * 1_000_000_000 + offset);
+
+        let mut trail = Trail::default();
+        trail.insert_segment(Position { x: 0, y: 0 }, Position { x: 1, y: 1 }, 5);
+        trail.insert_segment(Position { x: 4, y: 0 }, Position { x: -1, y: 1 }, 5);
+        trail.insert_segment(Position { x: 1, y: 0 }, Position { x: 1, y: 1 }, 3);
+        trail.insert(Position { x: 2, y: 2 });
+        trail.insert(Position { x: 9, y: 9 });
+        assert_eq!(trail.len(), 5 + 5 - 1 + 3 + 1);
+        assert!(trail.contains(&Position { x: 3, y: 2 }));
+        assert!(!trail.contains(&Position { x: 2, y: 3 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid move on line 2: UR x")]
+    fn day9_invalid_move() {
+        parse("R 4\nUR x\nL 2");
+    }
+
+    #[test]
+    fn day9_moves() {
+        for line in ["R 4", "UR 2", "DL 10", "by 3 -2", "to -7 0"] {
+            assert_eq!(line.parse::<Move>().unwrap().to_string(), line);
+        }
+        assert_eq!("RU 2".parse::<Move>(), Err(()));
+        assert_eq!("by 3".parse::<Move>(), Err(()));
+        assert_eq!("to 1 x".parse::<Move>(), Err(()));
+
+        // The same head path gives the same rope however it is written
+        let paths = ["UR 3\nR 2\nDL 4", "by 5 3\nby -4 -4", "to 5 3\nto 1 -1"];
+        let ropes: Vec<_> = paths
+            .iter()
+            .map(|path| simulate(path, 4, Mode::Stepwise))
+            .collect();
+        assert_eq!(ropes[0].knots, ropes[1].knots);
+        assert_eq!(ropes[0].knots, ropes[2].knots);
+        assert_eq!(ropes[0].tail_visited(), ropes[2].tail_visited());
+
+        let rope = simulate("UR 2", 3, Mode::Stepwise);
+        assert_eq!(
+            rope.knots,
+            [
+                Position { x: 2, y: 2 },
+                Position { x: 1, y: 1 },
+                Position::default()
+            ]
+        );
+
+        let rope = Rope::new(2);
+        assert_eq!(
+            rope.runs(Move::To(Position { x: -2, y: 5 })),
+            [(Position { x: -1, y: 1 }, 2), (Position { x: 0, y: 1 }, 3)]
+        );
+        assert_eq!(rope.runs(Move::By(Position::default())), []);
+
+        let mixed = "R 30\nUR 25\nby -40 7\nDL 12\nto 3 3\nU 18\nto -20 -20\nDR 50\nL 9";
+        for count in [1, 2, 5, 10] {
+            let stepwise = simulate(mixed, count, Mode::Stepwise);
+            let accelerated = simulate(mixed, count, Mode::Accelerated);
+
+            assert_eq!(stepwise.knots, accelerated.knots);
+            for knot in 0..count {
+                assert_eq!(stepwise.visited(knot), accelerated.visited(knot));
+            }
+        }
+
+        // Followers settle beside their leader rather than diagonally behind it
+        for count in [2, 3, 10] {
+            let turn = |n: usize| format!("R 5\nUR {n}\nL 7\nDR {n}");
+            let stepwise = simulate(&turn(2000), count, Mode::Stepwise);
+            let accelerated = simulate(&turn(2000), count, Mode::Accelerated);
+
+            assert_eq!(stepwise.knots, accelerated.knots);
+            for knot in 0..count {
+                assert_eq!(stepwise.visited(knot), accelerated.visited(knot));
+            }
+        }
+
+        let start = std::time::Instant::now();
+        let rope = simulate("R 5\nUR 1000000000", 2, Mode::Accelerated);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            rope.knots,
+            [
+                Position {
+                    x: 1_000_000_005,
+                    y: 1_000_000_000
+                },
+                Position {
+                    x: 1_000_000_004,
+                    y: 1_000_000_000
+                }
+            ]
+        );
+        assert_eq!(rope.visited(0), 1_000_000_006);
+        assert_eq!(rope.tail_visited(), 1_000_000_005);
+
+        let frames = animate("by 2 1", 2, Viewport::centered(Position::default(), 5, 5));
+        assert_eq!(frames.len(), 3);
+        assert!(frames[2].starts_with("== by 2 1 (2/2) =="));
+    }
 }